@@ -0,0 +1,187 @@
+use crate::{
+    level::{Level, LevelLossReason},
+    node::NodeType,
+    render::{Cell, Frame},
+    renderer,
+    settings::Settings,
+};
+use crossterm::style::Color;
+use std::{thread, time::Duration};
+
+/// Milliseconds each frame of a flash effect is shown for.
+const FLASH_TIME: u64 = 80;
+
+/// Number of times a node flashes when it is lit, shot or zapped.
+const FLASH_COUNT: usize = 3;
+
+/// Glyphs cycled through by the zapper and death effects.
+const BURST: [char; 4] = ['*', '+', 'x', '.'];
+
+fn sleep(millis: u64) {
+    thread::sleep(Duration::from_millis(millis));
+}
+
+/// Sets the grid cell at `(row, col)` of a level's `screen` frame.
+fn put(screen: &mut Frame, offset: (u16, u16), row: u16, col: u16, cell: Cell) {
+    let cell_width = Settings::current().cell_width();
+    let mut grid = Frame::new(1, cell_width);
+    grid.put(0, 0, cell, cell_width);
+    for i in 0..cell_width {
+        let cell = grid.get(0, i);
+        if cell != Cell::default() {
+            screen.set(row + offset.0, col * cell_width + offset.1 + i, cell);
+        }
+    }
+}
+
+/// Returns `true` if the node at `index` is a player, statue or zapper
+/// that was just hit by a laser beam.
+fn was_hit(level: &Level, previous: &Level, index: usize) -> bool {
+    matches!(
+        level.nodes[index].node_type,
+        NodeType::Player(_) | NodeType::Statue(_) | NodeType::Zapper(_)
+    ) && level.nodes[index].state()
+        && !previous.nodes[index].state()
+}
+
+/// Draws the laser beams of `level` that changed since `previous` cell by
+/// cell, starting from where the old beam diverges, then flashes any
+/// statues that were lit along the way. `level` must already be updated
+/// and should be redrawn by the caller afterwards.
+pub fn sweep_beams(level: &Level, previous: &Level) {
+    let settings = Settings::current();
+    if !settings.animations {
+        return;
+    }
+    let mut start = level.clone();
+    let mut sweeps: Vec<(usize, usize)> = vec![];
+    let mut hit: Vec<usize> = vec![];
+    for i in 0..level.nodes.len() {
+        if was_hit(level, previous, i) {
+            start.nodes[i].set_state(false);
+            hit.push(i);
+        }
+        let (new, old) = match (&level.nodes[i].node_type, &previous.nodes[i].node_type) {
+            (NodeType::Laser(new), NodeType::Laser(old)) => (&new.shooting_at, &old.shooting_at),
+            _ => continue,
+        };
+        let common = new
+            .iter()
+            .zip(old.iter())
+            .take_while(|(n, o)| n == o)
+            .count();
+        if common < new.len() {
+            start.nodes[i].set_shooting_at(new[..common].to_vec());
+            sweeps.push((i, common.saturating_sub(2)));
+        }
+    }
+    if sweeps.is_empty() && hit.is_empty() {
+        return;
+    }
+    let mut screen = start.screen();
+    renderer::present(&screen).ok();
+    let offset = level.offset();
+    let cell_width = settings.cell_width();
+    let beams: Vec<_> = sweeps
+        .iter()
+        .map(|&(i, from)| (level.nodes[i].beam_cells(), from))
+        .collect();
+    let frame = level.frame();
+    let steps = beams
+        .iter()
+        .map(|(cells, from)| cells.len().saturating_sub(*from))
+        .max()
+        .unwrap_or(0);
+    for step in 0..steps {
+        for (cells, from) in beams.iter() {
+            if let Some(&(row, col, _)) = cells.get(from + step) {
+                if hit
+                    .iter()
+                    .any(|&i| level.nodes[i].row == row && level.nodes[i].col == col)
+                {
+                    continue;
+                }
+                for i in 0..cell_width {
+                    let col = col * cell_width + i;
+                    screen.set(row + offset.0, col + offset.1, frame.get(row, col));
+                }
+            }
+        }
+        renderer::present(&screen).ok();
+        sleep(settings.animation_speed);
+    }
+    flash(&mut screen, level, &start, &hit);
+}
+
+/// Alternates the given nodes on `screen` between their state in `from`
+/// and `to`.
+fn flash(screen: &mut Frame, to: &Level, from: &Level, nodes: &[usize]) {
+    if nodes.is_empty() {
+        return;
+    }
+    let offset = to.offset();
+    for i in 0..(FLASH_COUNT * 2) {
+        let level = if i % 2 == 0 { from } else { to };
+        for &n in nodes.iter() {
+            if let Some(cell) = level.nodes[n].cell() {
+                put(screen, offset, level.nodes[n].row, level.nodes[n].col, cell);
+            }
+        }
+        renderer::present(screen).ok();
+        sleep(FLASH_TIME);
+    }
+}
+
+/// Flashes every statue in the level once it has been won.
+pub fn win(level: &Level) {
+    if !Settings::current().animations {
+        return;
+    }
+    let mut unlit = level.clone();
+    let mut statues: Vec<usize> = vec![];
+    for (i, node) in level.nodes.iter().enumerate() {
+        if let NodeType::Statue(s) = &node.node_type {
+            unlit.nodes[i].set_state(!s.lit);
+            statues.push(i);
+        }
+    }
+    flash(&mut level.screen(), level, &unlit, &statues);
+}
+
+/// Plays a short burst over the lit zappers or the player that was shot
+/// depending on why the level was lost.
+pub fn loss(level: &Level, reason: &LevelLossReason) {
+    if !Settings::current().animations {
+        return;
+    }
+    let targets: Vec<_> = level
+        .nodes
+        .iter()
+        .filter(|n| match (&n.node_type, reason) {
+            (NodeType::Zapper(z), LevelLossReason::Zapper) => z.lit,
+            (NodeType::Player(p), LevelLossReason::Death) => p.dead,
+            _ => false,
+        })
+        .collect();
+    let offset = level.offset();
+    let mut screen = level.screen();
+    for i in 0..(BURST.len() * 2) {
+        for node in targets.iter() {
+            let color = if i % 2 == 0 {
+                Color::Yellow
+            } else {
+                Color::Red
+            };
+            put(
+                &mut screen,
+                offset,
+                node.row,
+                node.col,
+                Cell::new(BURST[i % BURST.len()], color, Color::Black),
+            );
+        }
+        renderer::present(&screen).ok();
+        sleep(FLASH_TIME);
+    }
+    renderer::present(&level.screen()).ok();
+}
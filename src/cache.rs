@@ -0,0 +1,261 @@
+use crate::replay;
+use reqwest::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long to wait for a server to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a whole request may take, including downloading the content.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How many more times a request is sent if the server can't be reached
+/// or has an error, waiting a little longer before each attempt.
+const RETRIES: u32 = 2;
+
+/// How long to wait before the first retry, doubled for each one after.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// How long to wait for a server to accept a connection when only
+/// fetching a preview.
+const QUICK_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a whole request may take when only fetching a preview.
+const QUICK_REQUEST_TIMEOUT: Duration = Duration::from_secs(4);
+
+/// How long a request can take before the progress callback is told
+/// that it is still waiting.
+const SLOW_REQUEST: Duration = Duration::from_secs(1);
+
+/// A function that shows a progress message, or clears it when given
+/// `None`.
+pub type ProgressCallback = fn(Option<&str>);
+
+/// Called with a message while a request is slow or being retried, and
+/// with `None` once it is done, so that the game doesn't look frozen.
+static PROGRESS: RwLock<Option<ProgressCallback>> = RwLock::new(None);
+
+/// How hard to try to download something before giving up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Patience {
+    /// Waits for slow servers and retries a few times, for content that
+    /// is needed to go on like a listing or a level that is about to be
+    /// played. Progress is reported while waiting.
+    Full,
+    /// Gives up after a single short attempt without reporting progress,
+    /// for content that is only nice to have like level previews.
+    Quick,
+}
+
+/// Where the content of a repository listing or level came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheStatus {
+    /// Downloaded because it wasn't cached or the cached copy was out of
+    /// date.
+    Downloaded,
+    /// The server said the cached copy is still up to date.
+    UpToDate,
+    /// The server couldn't be reached, so the cached copy downloaded at
+    /// `fetched_at` was used.
+    Offline { fetched_at: u64 },
+    /// Read from a repository on disk, which is never cached.
+    Local,
+}
+
+/// A response saved in `$HOME/.l1t/cache/`.
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// When the content was last downloaded or revalidated, in seconds
+    /// since the epoch.
+    fetched_at: u64,
+    content: String,
+}
+
+/// Returns the file a response from `url` is cached in, or `None` if the
+/// home directory can't be found.
+fn entry_path(url: &str) -> Option<PathBuf> {
+    let home = home::home_dir()?;
+    Some(home.join(format!(
+        ".l1t/cache/{:016x}.json",
+        replay::content_hash(url)
+    )))
+}
+
+fn read_entry(url: &str) -> Option<CacheEntry> {
+    let content = fs::read_to_string(entry_path(url)?).ok()?;
+    let entry: CacheEntry = serde_json::from_str(&content).ok()?;
+    if entry.url != url {
+        return None;
+    }
+    Some(entry)
+}
+
+/// Saves `entry`, ignoring any errors since the cache is only an
+/// optimization.
+fn write_entry(entry: &CacheEntry) {
+    let path = match entry_path(&entry.url) {
+        Some(p) => p,
+        None => return,
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).ok();
+    }
+    if let Ok(content) = serde_json::to_string(entry) {
+        fs::write(path, content).ok();
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Sets the function that is called with progress messages while a
+/// request is slow or being retried.
+pub fn on_progress(callback: ProgressCallback) {
+    if let Ok(mut p) = PROGRESS.write() {
+        *p = Some(callback);
+    }
+}
+
+fn progress(message: Option<&str>) {
+    if let Ok(p) = PROGRESS.read() {
+        if let Some(callback) = *p {
+            callback(message);
+        }
+    }
+}
+
+/// Sends a `GET` request to `url`, trying again after a short wait if the
+/// server can't be reached or responds with a server error as long as
+/// `patience` allows. Returns the result of the last attempt.
+async fn send(
+    url: &str,
+    cached: Option<&CacheEntry>,
+    patience: Patience,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let (connect_timeout, timeout, retries) = match patience {
+        Patience::Full => (CONNECT_TIMEOUT, REQUEST_TIMEOUT, RETRIES),
+        Patience::Quick => (QUICK_CONNECT_TIMEOUT, QUICK_REQUEST_TIMEOUT, 0),
+    };
+    let client = reqwest::Client::builder()
+        .connect_timeout(connect_timeout)
+        .timeout(timeout)
+        .build()?;
+    let mut reported = false;
+    let mut attempt = 0;
+    let result = loop {
+        let mut request = client.get(url);
+        if let Some(entry) = cached {
+            if let Some(etag) = &entry.etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let request = request.send();
+        tokio::pin!(request);
+        let result = tokio::select! {
+            result = &mut request => result,
+            _ = tokio::time::sleep(SLOW_REQUEST), if patience == Patience::Full => {
+                progress(Some(&format!("Waiting for {url}...")));
+                reported = true;
+                request.await
+            }
+        };
+        let reason = match &result {
+            Ok(r) if r.status().is_server_error() => r.status().to_string(),
+            Ok(_) => break result,
+            Err(e) if e.is_timeout() => "timed out".to_string(),
+            Err(e) if e.is_connect() => "couldn't connect to the server".to_string(),
+            Err(e) if e.is_request() => e.to_string(),
+            Err(_) => break result,
+        };
+        if attempt >= retries {
+            break result;
+        }
+        let delay = RETRY_DELAY * 2u32.pow(attempt);
+        progress(Some(&format!(
+            "{url}: {reason}, trying again in {:.1}s (attempt {} of {})...",
+            delay.as_secs_f32(),
+            attempt + 2,
+            retries + 1
+        )));
+        reported = true;
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    };
+    if reported {
+        progress(None);
+    }
+    result
+}
+
+/// Downloads the content at `url`, using the copy cached from the last
+/// download if the server says it hasn't changed through its `ETag` or
+/// `Last-Modified` headers. If the server can't be reached or has an
+/// error after a few tries, the cached copy is used no matter how old it
+/// is.
+pub async fn get(url: &str, patience: Patience) -> Result<(String, CacheStatus), Box<dyn Error>> {
+    let cached = read_entry(url);
+    let response = match send(url, cached.as_ref(), patience).await {
+        Ok(r) if !r.status().is_server_error() => r,
+        result => {
+            return match cached {
+                Some(entry) => Ok((
+                    entry.content,
+                    CacheStatus::Offline {
+                        fetched_at: entry.fetched_at,
+                    },
+                )),
+                None => match result {
+                    Ok(r) => Err(format!("{url}: {}", r.status()).into()),
+                    Err(e) if e.is_timeout() => Err(format!("{url}: timed out").into()),
+                    Err(e) if e.is_connect() => {
+                        Err(format!("{url}: couldn't connect to the server").into())
+                    }
+                    Err(e) => Err(e.into()),
+                },
+            };
+        }
+    };
+    if let (StatusCode::NOT_MODIFIED, Some(mut entry)) = (response.status(), cached) {
+        entry.fetched_at = now();
+        write_entry(&entry);
+        return Ok((entry.content, CacheStatus::UpToDate));
+    }
+    if !response.status().is_success() {
+        return Err(format!("{url}: {}", response.status()).into());
+    }
+    let header_value = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+    let etag = header_value(header::ETAG);
+    let last_modified = header_value(header::LAST_MODIFIED);
+    let content = match response.text().await {
+        Ok(c) => c,
+        Err(e) => return Err(format!("{url}: {e}").into()),
+    };
+    write_entry(&CacheEntry {
+        url: url.to_string(),
+        etag,
+        last_modified,
+        fetched_at: now(),
+        content: content.clone(),
+    });
+    Ok((content, CacheStatus::Downloaded))
+}
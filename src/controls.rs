@@ -1,5 +1,9 @@
-use crossterm::event::{read, Event, KeyCode, KeyEventKind};
+use crate::keymap::Keymap;
+use crossterm::event::{poll, read, Event, KeyCode, KeyEventKind, MouseButton, MouseEventKind};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Control {
     Up,
     Down,
@@ -8,32 +12,158 @@ pub enum Control {
     Help,
     Quit,
     Action,
+    Restart,
+    Undo,
     Select,
     GotoTop,
     GotoBottom,
+    ToggleWidth,
+    Search,
+    Filter,
+    Sort,
+    /// The left mouse button was pressed at the screen `(row, col)`.
+    Click(u16, u16),
+    ScrollUp,
+    ScrollDown,
+    /// Toggles the single node at the grid `(row, col)` next to the
+    /// player. Sent by the level when an adjacent node is clicked.
+    ToggleAt(u16, u16),
     None,
 }
 
 impl Control {
+    /// Every control that can be bound to a key, in the order they are
+    /// listed in the help menu.
+    pub const BINDABLE: [Control; 16] = [
+        Control::Up,
+        Control::Down,
+        Control::Left,
+        Control::Right,
+        Control::Action,
+        Control::Undo,
+        Control::Restart,
+        Control::Help,
+        Control::ToggleWidth,
+        Control::Quit,
+        Control::Select,
+        Control::GotoTop,
+        Control::GotoBottom,
+        Control::Search,
+        Control::Filter,
+        Control::Sort,
+    ];
+
+    /// Returns the name used for the control in the keymap file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Control::Up => "up",
+            Control::Down => "down",
+            Control::Left => "left",
+            Control::Right => "right",
+            Control::Help => "help",
+            Control::Quit => "quit",
+            Control::Action => "action",
+            Control::Restart => "restart",
+            Control::Undo => "undo",
+            Control::Select => "select",
+            Control::GotoTop => "goto_top",
+            Control::GotoBottom => "goto_bottom",
+            Control::ToggleWidth => "toggle_width",
+            Control::Search => "search",
+            Control::Filter => "filter",
+            Control::Sort => "sort",
+            Control::Click(_, _) => "click",
+            Control::ScrollUp => "scroll_up",
+            Control::ScrollDown => "scroll_down",
+            Control::ToggleAt(_, _) => "toggle_at",
+            Control::None => "none",
+        }
+    }
+
+    /// Returns the bindable control with the given keymap file name.
+    pub fn from_name(name: &str) -> Option<Control> {
+        Control::BINDABLE.into_iter().find(|c| c.name() == name)
+    }
+
+    /// Returns what the control does, as shown in the help menu.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Control::Up => "Move Up",
+            Control::Down => "Move Down",
+            Control::Left => "Move Left",
+            Control::Right => "Move Right",
+            Control::Help => "Show this help menu",
+            Control::Quit => "Pause the level or leave a menu",
+            Control::Action => "Toggle surrounding blocks (if able)",
+            Control::Restart => "Restart the level",
+            Control::Undo => "Undo the last move or action",
+            Control::Select => "Select a menu item",
+            Control::GotoTop => "Go to the top of a menu",
+            Control::GotoBottom => "Go to the bottom of a menu",
+            Control::ToggleWidth => "Toggle double width cells",
+            Control::Search => "Search a list of levels",
+            Control::Filter => "Change which levels are listed",
+            Control::Sort => "Change how levels are sorted",
+            Control::Click(_, _) => "Select, walk to or toggle what was clicked",
+            Control::ScrollUp => "Scroll up",
+            Control::ScrollDown => "Scroll down",
+            Control::ToggleAt(_, _) => "Toggle a block next to the player",
+            Control::None => "",
+        }
+    }
+
     pub fn read_input() -> Self {
-        if let Ok(Event::Key(event)) = read() {
-            if event.kind == KeyEventKind::Release {
-                return Self::None;
+        match read() {
+            Ok(Event::Key(event)) => {
+                if event.kind == KeyEventKind::Release {
+                    return Self::None;
+                }
+                Keymap::current().control(&event)
             }
-            return match event.code {
-                KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('k') => Self::Up,
-                KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('j') => Self::Down,
-                KeyCode::Left | KeyCode::Char('a') | KeyCode::Char('h') => Self::Left,
-                KeyCode::Right | KeyCode::Char('d') | KeyCode::Char('l') => Self::Right,
-                KeyCode::Char('g') => Self::GotoTop,
-                KeyCode::Char('G') => Self::GotoBottom,
-                KeyCode::Char(' ') => Self::Action,
-                KeyCode::Char('H') => Self::Help,
-                KeyCode::Char('q') => Self::Quit,
-                KeyCode::Enter => Self::Select,
+            Ok(Event::Mouse(event)) => match event.kind {
+                MouseEventKind::Down(MouseButton::Left) => Self::Click(event.row, event.column),
+                MouseEventKind::ScrollUp => Self::ScrollUp,
+                MouseEventKind::ScrollDown => Self::ScrollDown,
                 _ => Self::None,
-            };
+            },
+            _ => Self::None,
+        }
+    }
+
+    /// Same as `Control::read_input` but returns `None` if no input is
+    /// received within the `timeout`.
+    pub fn poll_input(timeout: Duration) -> Option<Self> {
+        match poll(timeout) {
+            Ok(false) => None,
+            _ => Some(Self::read_input()),
+        }
+    }
+}
+
+/// A key pressed while typing text, such as a search query.
+pub enum TextInput {
+    Char(char),
+    Backspace,
+    /// `Enter` was pressed.
+    Done,
+    /// `Esc` was pressed.
+    Cancel,
+    None,
+}
+
+impl TextInput {
+    /// Waits for a key press, ignoring the keymap so that every
+    /// character can be typed.
+    pub fn read() -> TextInput {
+        match read() {
+            Ok(Event::Key(event)) if event.kind != KeyEventKind::Release => match event.code {
+                KeyCode::Char(c) => TextInput::Char(c),
+                KeyCode::Backspace => TextInput::Backspace,
+                KeyCode::Enter => TextInput::Done,
+                KeyCode::Esc => TextInput::Cancel,
+                _ => TextInput::None,
+            },
+            _ => TextInput::None,
         }
-        Self::None
     }
 }
@@ -0,0 +1,339 @@
+use crate::controls::Control;
+use crossterm::event::{read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use std::{collections::BTreeMap, fmt, fs, path, sync::RwLock};
+
+/// A key press, including any `Ctrl` or `Alt` modifiers. `Shift` is
+/// part of the character itself, so `H` is `Shift-h`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub const fn new(code: KeyCode) -> Key {
+        Key {
+            code,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    pub const fn char(c: char) -> Key {
+        Key::new(KeyCode::Char(c))
+    }
+
+    /// Returns the key that was pressed in `event`.
+    pub fn from_event(event: &KeyEvent) -> Key {
+        let mut modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        let code = match event.code {
+            KeyCode::Char(c) if event.modifiers.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            KeyCode::BackTab => {
+                modifiers |= KeyModifiers::SHIFT;
+                KeyCode::Tab
+            }
+            code => code,
+        };
+        Key { code, modifiers }
+    }
+
+    /// Waits for a key press and returns the key, or `None` if `Esc` was
+    /// pressed.
+    pub fn read() -> Option<Key> {
+        loop {
+            if let Ok(Event::Key(event)) = read() {
+                if event.kind == KeyEventKind::Release {
+                    continue;
+                }
+                return match event.code {
+                    KeyCode::Esc => None,
+                    _ => Some(Key::from_event(&event)),
+                };
+            }
+        }
+    }
+
+    /// Parses a key as it is written in the keymap file, such as `w`,
+    /// `Shift-h`, `Ctrl-r`, `Space` or `Up`.
+    pub fn parse(s: &str) -> Result<Key, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s.trim();
+        loop {
+            let (prefix, modifier) = match rest.split_once('-') {
+                Some((p, r)) if !r.is_empty() => (p, r),
+                _ => break,
+            };
+            match prefix.to_lowercase().as_str() {
+                "ctrl" => modifiers |= KeyModifiers::CONTROL,
+                "alt" => modifiers |= KeyModifiers::ALT,
+                "shift" => modifiers |= KeyModifiers::SHIFT,
+                _ => break,
+            }
+            rest = modifier;
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "enter" => KeyCode::Enter,
+                "space" => KeyCode::Char(' '),
+                "comma" => KeyCode::Char(','),
+                "tab" => KeyCode::Tab,
+                "esc" => KeyCode::Esc,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                f => match f.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key `{s}`")),
+                },
+            },
+        };
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            code => code,
+        };
+        Ok(Key { code, modifiers })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(',') => write!(f, "Comma"),
+            KeyCode::Char(c) if c.is_ascii_uppercase() => {
+                write!(f, "Shift-{}", c.to_ascii_lowercase())
+            }
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+/// The keys bound to each `Control`.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Key, Control)>,
+}
+
+static KEYMAP: RwLock<Option<Keymap>> = RwLock::new(None);
+
+impl Keymap {
+    pub const DEFAULT_BINDINGS: [(Key, Control); 24] = [
+        (Key::char('w'), Control::Up),
+        (Key::char('k'), Control::Up),
+        (Key::new(KeyCode::Up), Control::Up),
+        (Key::char('s'), Control::Down),
+        (Key::char('j'), Control::Down),
+        (Key::new(KeyCode::Down), Control::Down),
+        (Key::char('a'), Control::Left),
+        (Key::char('h'), Control::Left),
+        (Key::new(KeyCode::Left), Control::Left),
+        (Key::char('d'), Control::Right),
+        (Key::char('l'), Control::Right),
+        (Key::new(KeyCode::Right), Control::Right),
+        (Key::char(' '), Control::Action),
+        (Key::char('u'), Control::Undo),
+        (Key::char('r'), Control::Restart),
+        (Key::char('H'), Control::Help),
+        (Key::new(KeyCode::Tab), Control::ToggleWidth),
+        (Key::char('q'), Control::Quit),
+        (Key::new(KeyCode::Enter), Control::Select),
+        (Key::char('g'), Control::GotoTop),
+        (Key::char('G'), Control::GotoBottom),
+        (Key::char('/'), Control::Search),
+        (Key::char('F'), Control::Filter),
+        (Key::char('O'), Control::Sort),
+    ];
+
+    /// Returns the keymap currently in use.
+    pub fn current() -> Keymap {
+        match KEYMAP.read() {
+            Ok(k) => k.clone().unwrap_or_default(),
+            Err(_) => Keymap::default(),
+        }
+    }
+
+    /// Replaces the keymap currently in use.
+    pub fn set(keymap: Keymap) {
+        if let Ok(mut k) = KEYMAP.write() {
+            *k = Some(keymap);
+        }
+    }
+
+    /// Returns the control bound to the key pressed in `event`.
+    pub fn control(&self, event: &KeyEvent) -> Control {
+        let key = Key::from_event(event);
+        self.bindings
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, c)| *c)
+            .unwrap_or(Control::None)
+    }
+
+    /// Returns every key bound to `control`.
+    pub fn keys(&self, control: Control) -> Vec<Key> {
+        self.bindings
+            .iter()
+            .filter(|(_, c)| *c == control)
+            .map(|(k, _)| *k)
+            .collect()
+    }
+
+    /// Parses the content of a keymap file. Each line binds a control to
+    /// a comma separated list of keys, e.g. `up = w, Up`, and
+    /// replaces all of the default keys for that control. Blank lines and
+    /// lines starting with `#` are ignored.
+    ///
+    /// Returns an error if a line can't be parsed or if a key ends up
+    /// bound to more than one control.
+    pub fn parse(content: &str) -> Result<Keymap, String> {
+        let mut bound: Vec<(Key, Control)> = vec![];
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, keys) = match line.split_once('=') {
+                Some(parts) => parts,
+                None => return Err(format!("line {}: expected `control = keys`", i + 1)),
+            };
+            let control = match Control::from_name(name.trim()) {
+                Some(c) => c,
+                None => return Err(format!("line {}: unknown control `{}`", i + 1, name.trim())),
+            };
+            for key in keys.split(',') {
+                let key = Key::parse(key).map_err(|e| format!("line {}: {e}", i + 1))?;
+                bound.push((key, control));
+            }
+        }
+        let mut bindings: Vec<(Key, Control)> = vec![];
+        for (key, control) in Keymap::DEFAULT_BINDINGS.iter() {
+            if !bound.iter().any(|(_, c)| c == control) {
+                bindings.push((*key, *control));
+            }
+        }
+        for (key, control) in bound.into_iter() {
+            if !bindings.contains(&(key, control)) {
+                bindings.push((key, control));
+            }
+        }
+        Keymap::check(bindings)
+    }
+
+    /// Returns a copy of the keymap with the keys of each control in
+    /// `keys`, by `Control::name`, replaced by the comma separated keys
+    /// it maps to.
+    ///
+    /// Returns an error if a control or key can't be parsed or if a key
+    /// ends up bound to more than one control.
+    pub fn with_keys(&self, keys: &BTreeMap<String, String>) -> Result<Keymap, String> {
+        let mut bound: Vec<(Key, Control)> = vec![];
+        for (name, list) in keys.iter() {
+            let control = match Control::from_name(name) {
+                Some(c) => c,
+                None => return Err(format!("unknown control `{name}`")),
+            };
+            for key in list.split(',') {
+                bound.push((Key::parse(key)?, control));
+            }
+        }
+        let mut bindings: Vec<(Key, Control)> = self
+            .bindings
+            .iter()
+            .filter(|(_, c)| !bound.iter().any(|(_, b)| b == c))
+            .cloned()
+            .collect();
+        for (key, control) in bound.into_iter() {
+            if !bindings.contains(&(key, control)) {
+                bindings.push((key, control));
+            }
+        }
+        Keymap::check(bindings)
+    }
+
+    /// Returns a keymap with `bindings`, or an error if a key is bound to
+    /// more than one control.
+    fn check(bindings: Vec<(Key, Control)>) -> Result<Keymap, String> {
+        for (i, (key, control)) in bindings.iter().enumerate() {
+            if let Some((_, other)) = bindings[..i].iter().find(|(k, _)| k == key) {
+                return Err(format!(
+                    "`{key}` is bound to both `{}` and `{}`",
+                    other.name(),
+                    control.name()
+                ));
+            }
+        }
+        Ok(Keymap { bindings })
+    }
+
+    /// Reads the keymap from `$HOME/.l1t/keymap.l1t_conf`, creating the
+    /// file with the default bindings commented out if it doesn't exist.
+    pub fn read(home_dir: String) -> Result<Keymap, String> {
+        let file = home_dir.to_string() + "/.l1t/keymap.l1t_conf";
+        if !path::Path::new(&file).exists() {
+            fs::create_dir(home_dir + "/.l1t").ok();
+            if let Err(e) = fs::write(&file, Keymap::default().template()) {
+                return Err(e.to_string());
+            }
+        }
+        let content = fs::read_to_string(&file).unwrap_or_default();
+        Keymap::parse(&content).map_err(|e| format!("{file}: {e}"))
+    }
+
+    /// Returns the content of a keymap file that lists every binding
+    /// of this keymap as a comment.
+    fn template(&self) -> String {
+        let mut out = String::from(
+            "# l1t keymap\n#\n# Bind a control to a comma separated list of keys, e.g.\n# `up = w, Up`. Listing a control replaces all of its default keys.\n#\n",
+        );
+        for control in Control::BINDABLE.iter() {
+            let keys: Vec<String> = self.keys(*control).iter().map(|k| k.to_string()).collect();
+            out += &format!("# {} = {}\n", control.name(), keys.join(", "));
+        }
+        out
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap {
+            bindings: Keymap::DEFAULT_BINDINGS.to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readme_dvorak_keymap_parses() {
+        let dvorak = "up = comma, Up\ndown = o, Down\nleft = a, Left\nright = e, Right\n";
+        assert!(Keymap::parse(dvorak).is_ok());
+    }
+}
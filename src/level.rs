@@ -1,16 +1,41 @@
-use crate::{controls::Control, direction::Direction, menu::*, node::*, repository::Repository};
-use crossterm::{
-    cursor, execute,
-    style::{Color, Print, SetBackgroundColor, SetForegroundColor, Stylize},
-    terminal::{size, Clear, ClearType},
-    ExecutableCommand,
+use crate::{
+    animation,
+    cache::Patience,
+    controls::Control,
+    direction::Direction,
+    menu::*,
+    node::*,
+    render::{Cell, Frame},
+    renderer,
+    replay::{self, Replay},
+    repository::Repository,
+    settings::Settings,
+    solver,
 };
+use crossterm::style::{Color, Stylize};
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::{BTreeMap, VecDeque},
     fs,
-    io::stdout,
     path::{Path, PathBuf},
+    sync::{Arc, OnceLock, RwLock},
+    thread,
+    time::{Duration, Instant},
 };
 
+/// The par of every level searched for so far, by the hash of its grid,
+/// so that it is only searched for once however many times the level is
+/// loaded.
+static PARS: RwLock<BTreeMap<u64, Arc<OnceLock<Option<usize>>>>> = RwLock::new(BTreeMap::new());
+
+/// How often the HUD is redrawn while waiting for input so the timer
+/// keeps ticking.
+const HUD_REFRESH: Duration = Duration::from_secs(1);
+
+/// How long the player pauses on each cell when walking to a clicked
+/// position.
+const WALK_TIME: Duration = Duration::from_millis(60);
+
 #[derive(Debug)]
 pub enum LevelLossReason {
     Zapper,
@@ -18,20 +43,61 @@ pub enum LevelLossReason {
     Death,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum LevelSource {
     File(PathBuf),
     Url(String),
     Core(usize),
 }
 
-#[derive(Debug)]
+impl LevelSource {
+    /// Returns a string that uniquely identifies the source, used to
+    /// store things like saved games in the user data. File paths are
+    /// canonicalized so the same file always has the same key.
+    pub fn key(&self) -> String {
+        match self {
+            LevelSource::File(path) => {
+                let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+                format!("file:{}", path.to_string_lossy())
+            }
+            LevelSource::Url(url) => url.to_string(),
+            LevelSource::Core(level) => format!("core:{level}"),
+        }
+    }
+
+    /// Returns the key of the score of the level from this source with
+    /// the `Level::hash` `hash` in the user data. Levels loaded from files
+    /// include the hash so that editing a level starts a new score, the
+    /// same as it resets the level's completion.
+    pub fn score_key(&self, hash: &str) -> String {
+        match self {
+            LevelSource::File(_) => format!("{}#{hash}", self.key()),
+            _ => self.key(),
+        }
+    }
+}
+
+/// The position and toggle state of every node in a level, in the same
+/// order as `Level::nodes`.
+pub type Snapshot = Vec<(u16, u16, bool)>;
+
+#[derive(Debug, Clone)]
 pub struct Level {
     pub info: LevelInfo,
     pub nodes: Vec<Node>,
     pub rows: u16,
     pub cols: u16,
     pub player_index: Option<usize>,
+    pub stats: LevelStats,
+    /// The state of the level and the move and action counters before
+    /// each step, most recent last, used to undo steps.
+    history: Vec<(Snapshot, usize, usize)>,
+    /// The state of the level when it was loaded, used to restart it.
+    initial: Snapshot,
+    /// `replay::content_hash` of the level's grid.
+    hash: u64,
+    /// Every gameplay control applied with `Level::step`, in order.
+    recording: Vec<Control>,
 }
 
 #[derive(Debug, Clone)]
@@ -40,19 +106,182 @@ pub struct LevelInfo {
     pub name: String,
     pub author: String,
     pub description: String,
+    pub metadata: LevelMetadata,
+}
+
+/// Optional `key: value` information about a level. In level files these
+/// are written on the lines between the description and the grid, and in
+/// repository listings they are extra fields on each level.
+#[derive(Debug, Clone, Default)]
+pub struct LevelMetadata {
+    /// The number of moves and actions needed to beat the level.
+    pub par: Option<usize>,
+    /// Words used to group levels, written as a comma separated list.
+    pub tags: Vec<String>,
+    /// How hard the level is, higher is harder.
+    pub difficulty: Option<usize>,
+    /// When the level was published, as `YYYY-MM-DD`.
+    pub date: Option<String>,
+}
+
+/// Counters for the current attempt at a level.
+#[derive(Debug, Clone)]
+pub struct LevelStats {
+    /// Number of times the player moved to a new space.
+    pub moves: usize,
+    /// Number of times the player toggled their surrounding blocks.
+    pub actions: usize,
+    /// Number of times the level was restarted with `Control::Restart`.
+    pub restarts: usize,
+    /// Number of steps taken back with `Control::Undo`.
+    pub undos: usize,
+    pub started_at: Instant,
+}
+
+/// A level left with `Save & Quit` from the pause menu so that it can be
+/// resumed the next time it is played.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedLevel {
+    /// The `LevelSource::key` of the level.
+    pub source: String,
+    pub snapshot: Snapshot,
+    pub moves: usize,
+    pub actions: usize,
+    pub restarts: usize,
+    pub undos: usize,
+    /// Seconds spent playing the level before it was saved.
+    pub elapsed: u64,
+    /// The controls recorded before the level was saved.
+    #[serde(default)]
+    pub controls: Vec<Control>,
 }
 
 #[derive(Debug)]
 pub struct LevelResult {
     pub has_won: bool,
     pub reason_for_loss: Option<LevelLossReason>,
+    pub moves: usize,
+    pub actions: usize,
+    pub restarts: usize,
+    pub undos: usize,
+    /// Time spent playing the attempt, not counting time paused.
+    pub time: Duration,
+    /// The level's par, only computed for won levels.
+    pub par: Option<usize>,
+    /// Between 1 and 3 for won levels, see `LevelResult::rate`, and 0
+    /// otherwise or if the level has no par.
+    pub stars: usize,
+    /// Set if the player chose `Save & Quit` from the pause menu.
+    pub saved: Option<SavedLevel>,
+    /// Every control applied during the attempt.
+    pub replay: Replay,
 }
 
 #[derive(Debug)]
-struct PlayState {
-    is_playing: bool,
-    has_won: bool,
-    reason_for_loss: Option<LevelLossReason>,
+pub(crate) struct PlayState {
+    pub(crate) is_playing: bool,
+    pub(crate) has_won: bool,
+    pub(crate) reason_for_loss: Option<LevelLossReason>,
+}
+
+impl LevelResult {
+    /// Returns the stars earned for winning a level with a par of `par`
+    /// using `score` moves, actions and undos: 3 for meeting the par, 2
+    /// for staying within one and a half times the par and 1 otherwise.
+    pub fn rate(score: usize, par: usize) -> usize {
+        if score <= par {
+            3
+        } else if score * 2 <= par * 3 {
+            2
+        } else {
+            1
+        }
+    }
+}
+
+/// Returns `stars` out of 3 as filled and empty stars, e.g. `★★☆`.
+pub fn star_rating(stars: usize) -> String {
+    let stars = stars.min(3);
+    "\u{2605}".repeat(stars) + &"\u{2606}".repeat(3 - stars)
+}
+
+impl LevelMetadata {
+    /// Parses a single `key: value` metadata line. Returns `false` if the
+    /// line is not metadata. Unknown keys are ignored.
+    pub fn parse_line(&mut self, line: &str) -> bool {
+        if line.starts_with('I') {
+            return false;
+        }
+        let (key, value) = match line.split_once(':') {
+            Some(kv) => kv,
+            None => return false,
+        };
+        let value = value.trim();
+        match key.trim() {
+            "par" => self.par = value.parse().ok(),
+            "tags" => {
+                self.tags = value
+                    .split(',')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect()
+            }
+            "difficulty" => self.difficulty = value.parse().ok(),
+            "date" => self.date = Some(value.to_string()),
+            _ => (),
+        }
+        true
+    }
+}
+
+impl LevelStats {
+    pub fn new() -> LevelStats {
+        LevelStats {
+            moves: 0,
+            actions: 0,
+            restarts: 0,
+            undos: 0,
+            started_at: Instant::now(),
+        }
+    }
+}
+
+impl Default for LevelStats {
+    fn default() -> LevelStats {
+        LevelStats::new()
+    }
+}
+
+impl LevelInfo {
+    /// Parses the `name`, `author`, `description` and metadata lines at the
+    /// start of a level file. Returns the info and the index of the first
+    /// line of the grid.
+    fn parse_header(content: &[&str], source: LevelSource) -> (LevelInfo, usize) {
+        let line = |i: usize| content.get(i).unwrap_or(&"").trim_end().to_string();
+        let mut metadata = LevelMetadata::default();
+        let mut grid_start = 3;
+        while grid_start < content.len() && metadata.parse_line(content[grid_start]) {
+            grid_start += 1;
+        }
+        (
+            LevelInfo {
+                source,
+                name: line(0),
+                author: line(1),
+                description: line(2),
+                metadata,
+            },
+            grid_start,
+        )
+    }
+
+    /// Returns whether the content of a level file starts with a header,
+    /// rather than with the top wall of the grid as level files served by
+    /// repositories usually do.
+    pub fn has_header(content: &str) -> bool {
+        let first = content.trim().lines().next().unwrap_or("").trim_end();
+        first.is_empty() || first.chars().any(|c| c != 'I')
+    }
 }
 
 impl Level {
@@ -117,56 +346,121 @@ I1    X    SI
 IIIIIIIIIIIII",
     ];
 
-    fn draw_walls(&self, row_offset: u16, col_offset: u16) -> crossterm::Result<()> {
-        let mut stdout = stdout();
-        for r in row_offset..(self.rows + row_offset) {
-            for c in col_offset..(self.cols + col_offset) {
-                if r == row_offset
-                    || r == self.rows + row_offset - 1
-                    || c == col_offset
-                    || c == self.cols + col_offset - 1
-                {
-                    execute!(
-                        stdout,
-                        SetForegroundColor(Color::White),
-                        SetBackgroundColor(Color::White),
-                        cursor::MoveTo(c, r),
-                        Print('I'.bold()),
-                    )?;
-                }
-            }
-        }
-        execute!(
-            stdout,
-            SetForegroundColor(Color::Reset),
-            SetBackgroundColor(Color::Reset),
+    /// Returns the screen `(row, col)` of the top left corner of the
+    /// level so that it is centered on screen.
+    pub(crate) fn offset(&self) -> (u16, u16) {
+        let (term_cols, term_rows) = renderer::size();
+        let cols = self.cols * Settings::current().cell_width();
+        (
+            term_rows.saturating_sub(self.rows) / 2,
+            term_cols.saturating_sub(cols) / 2,
         )
     }
 
-    fn draw_nodes(&self, row_offset: u16, col_offset: u16) -> crossterm::Result<()> {
-        for i in 0..self.nodes.len() {
-            self.nodes[i].draw((row_offset, col_offset))?;
+    /// Builds the full screen shown while playing the level: the grid
+    /// centered on screen with the HUD around it.
+    pub(crate) fn screen(&self) -> Frame {
+        let mut frame = renderer::blank();
+        let (row_offset, col_offset) = self.offset();
+        frame.blit(&self.frame(), row_offset, col_offset);
+        self.draw_hud(&mut frame);
+        frame
+    }
+
+    pub(crate) fn draw(&self) -> crossterm::Result<()> {
+        renderer::present(&self.screen())
+    }
+
+    /// Draws the level's name, author and description above the grid and
+    /// the counters for the current attempt below it.
+    fn draw_hud(&self, frame: &mut Frame) {
+        let (row_offset, _) = self.offset();
+        let cols = frame.cols;
+        let centered = |text: &str| {
+            let text: String = text.chars().take(cols as usize).collect();
+            let col = cols.saturating_sub(text.chars().count() as u16) / 2;
+            (col, text)
+        };
+        if row_offset >= 3 {
+            let title = if self.info.author.is_empty() {
+                self.info.name.to_string()
+            } else {
+                format!("{} by {}", self.info.name, self.info.author)
+            };
+            let (col, title) = centered(&title);
+            let (desc_col, description) = centered(&self.info.description);
+            frame.print(row_offset - 3, col, &title.bold());
+            frame.print(row_offset - 2, desc_col, &description.italic());
+        }
+        let status_row = row_offset + self.rows + 1;
+        if status_row < frame.rows {
+            let elapsed = self.stats.started_at.elapsed().as_secs();
+            let (lit, total) = self.statues();
+            let mut status = format!(
+                "Moves: {}  Actions: {}  Time: {:02}:{:02}  Statues: {}/{}",
+                self.stats.moves,
+                self.stats.actions,
+                elapsed / 60,
+                elapsed % 60,
+                lit,
+                total
+            );
+            if let Some(par) = self.info.metadata.par {
+                status += &format!("  Par: {par}");
+            }
+            if self.stats.restarts > 0 {
+                status += &format!("  Restarts: {}", self.stats.restarts);
+            }
+            let (col, status) = centered(&status);
+            frame.print(status_row, col, &status.bold());
         }
-        Ok(())
     }
 
-    fn draw_node_overlays(&self, row_offset: u16, col_offset: u16) -> crossterm::Result<()> {
-        for i in 0..self.nodes.len() {
-            self.nodes[i].draw_overlay((row_offset, col_offset))?;
+    /// Returns the number of statues that are in their winning state (lit,
+    /// or unlit for reverse statues) and the total number of statues.
+    pub fn statues(&self) -> (usize, usize) {
+        let mut lit = 0;
+        let mut total = 0;
+        for node in self.nodes.iter() {
+            if let NodeType::Statue(s) = &node.node_type {
+                total += 1;
+                if s.lit != s.reversed {
+                    lit += 1;
+                }
+            }
         }
-        Ok(())
+        (lit, total)
+    }
+
+    /// Builds a `Frame` of the level grid with its walls, laser beams
+    /// and nodes, without any terminal offset. Each grid cell takes up
+    /// `Settings::cell_width` columns of the frame.
+    pub fn frame(&self) -> Frame {
+        self.grid_frame(Settings::current().cell_width())
     }
 
-    fn draw(&self) -> crossterm::Result<()> {
-        let mut stdout = stdout();
-        stdout.execute(Clear(ClearType::All))?;
-        let (term_cols, term_rows) = size().unwrap_or((0, 0));
-        let row_offset = (term_rows - self.rows) / 2;
-        let col_offset = (term_cols - self.cols) / 2;
-        self.draw_walls(row_offset, col_offset)?;
-        self.draw_node_overlays(row_offset, col_offset)?;
-        self.draw_nodes(row_offset, col_offset)?;
-        Ok(())
+    /// Draws the grid with every cell `cell_width` columns wide, no
+    /// matter what the settings say.
+    pub fn grid_frame(&self, cell_width: u16) -> Frame {
+        let mut frame = Frame::new(self.rows, self.cols * cell_width);
+        for r in 0..self.rows {
+            for c in 0..self.cols {
+                if r == 0 || r == self.rows - 1 || c == 0 || c == self.cols - 1 {
+                    frame.put(r, c, Cell::new('I', Color::White, Color::White), cell_width);
+                }
+            }
+        }
+        for node in self.nodes.iter() {
+            for (r, c, cell) in node.beam_cells() {
+                frame.put(r, c, cell, cell_width);
+            }
+        }
+        for node in self.nodes.iter() {
+            if let Some(cell) = node.cell() {
+                frame.put(node.row, node.col, cell, cell_width);
+            }
+        }
+        frame
     }
 
     fn set_lasers_shooting_at(&mut self) {
@@ -253,6 +547,24 @@ IIIIIIIIIIIII",
         nodes
     }
 
+    fn toggle_at(&mut self, pos: (u16, u16)) {
+        let player_index = match self.player_index {
+            Some(i) => i,
+            None => return,
+        };
+        let player = (self.nodes[player_index].row, self.nodes[player_index].col);
+        if let Some(i) = self.node_index_at(pos) {
+            if !self.surrounding_nodes(player).contains(&i) || !self.nodes[i].is_player_toggleable()
+            {
+                return;
+            }
+            self.nodes[i].toggle();
+            if let NodeType::Switch(_) = &self.nodes[i].node_type {
+                self.toggle_blocks();
+            }
+        }
+    }
+
     fn player_action(&mut self) {
         let player_index = match self.player_index {
             Some(i) => i,
@@ -291,29 +603,114 @@ IIIIIIIIIIIII",
         pos.0 >= 1 && pos.0 < self.rows - 1 && pos.1 >= 1 && pos.1 < self.cols - 1
     }
 
-    fn move_player(&mut self, dir: Direction) {
+    fn move_player(&mut self, dir: Direction) -> bool {
         let player_index = match self.player_index {
             Some(i) => i,
-            None => return,
+            None => return false,
         };
         let new_pos = self.nodes[player_index].would_move_to(dir);
         if !self.is_valid_pos(new_pos) {
-            return;
+            return false;
         }
         if let Some(i) = self.node_index_at(new_pos) {
             if !self.nodes[i].is_moveable() {
-                return;
+                return false;
             }
             let new_pos = self.nodes[i].would_move_to(dir);
             if !self.is_valid_pos(new_pos) {
-                return;
+                return false;
             }
             if self.node_index_at(new_pos).is_some() {
-                return;
+                return false;
             }
             self.nodes[i].move_in_dir(dir);
         }
         self.nodes[player_index].move_in_dir(dir);
+        true
+    }
+
+    /// Returns the grid `(row, col)` drawn at the screen `(row, col)`, if
+    /// it is inside the level.
+    fn grid_pos(&self, screen_row: u16, screen_col: u16) -> Option<(u16, u16)> {
+        let (row_offset, col_offset) = self.offset();
+        if screen_row < row_offset || screen_col < col_offset {
+            return None;
+        }
+        let pos = (
+            screen_row - row_offset,
+            (screen_col - col_offset) / Settings::current().cell_width(),
+        );
+        if pos.0 < self.rows && pos.1 < self.cols {
+            Some(pos)
+        } else {
+            None
+        }
+    }
+
+    /// Finds the shortest list of moves that walks the player to `to`
+    /// through empty grid spaces, without pushing anything.
+    fn walk_to(&self, to: (u16, u16)) -> Option<Vec<Control>> {
+        let player_index = self.player_index?;
+        let from = (self.nodes[player_index].row, self.nodes[player_index].col);
+        if !self.is_valid_pos(to) || self.node_index_at(to).is_some() {
+            return None;
+        }
+        let moves = [
+            (Direction::UP, Control::Up),
+            (Direction::DOWN, Control::Down),
+            (Direction::LEFT, Control::Left),
+            (Direction::RIGHT, Control::Right),
+        ];
+        let cols = self.cols as usize;
+        let mut parents: Vec<Option<((u16, u16), Control)>> = vec![None; self.rows as usize * cols];
+        let mut queue = VecDeque::from([from]);
+        while let Some(pos) = queue.pop_front() {
+            if pos == to {
+                let mut path = vec![];
+                let mut current = pos;
+                while let Some((parent, control)) =
+                    parents[current.0 as usize * cols + current.1 as usize]
+                {
+                    path.push(control);
+                    current = parent;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for (dir, control) in moves.iter() {
+                let next = ((pos.0 as i16 + dir.0) as u16, (pos.1 as i16 + dir.1) as u16);
+                if next == from
+                    || !self.is_valid_pos(next)
+                    || self.node_index_at(next).is_some()
+                    || parents[next.0 as usize * cols + next.1 as usize].is_some()
+                {
+                    continue;
+                }
+                parents[next.0 as usize * cols + next.1 as usize] = Some((pos, *control));
+                queue.push_back(next);
+            }
+        }
+        None
+    }
+
+    /// Returns the controls that carry out a click on the grid `pos`:
+    /// toggling the node there if it is next to the player, or walking
+    /// the player to it.
+    fn click(&self, pos: (u16, u16)) -> Vec<Control> {
+        let player_index = match self.player_index {
+            Some(i) => i,
+            None => return vec![],
+        };
+        let player = &self.nodes[player_index];
+        if let Some(i) = self.node_index_at(pos) {
+            if self.nodes[i].is_player_toggleable()
+                && player.row.abs_diff(pos.0) + player.col.abs_diff(pos.1) == 1
+            {
+                return vec![Control::ToggleAt(pos.0, pos.1)];
+            }
+            return vec![];
+        }
+        self.walk_to(pos).unwrap_or_default()
     }
 
     fn reset_statues(&mut self) {
@@ -324,7 +721,249 @@ IIIIIIIIIIIII",
         }
     }
 
-    fn play_state(&self) -> PlayState {
+    /// Recomputes every laser beam and the statues they light. Must be
+    /// called after the level is loaded and after every `Level::apply`.
+    pub fn update(&mut self) {
+        self.reset_statues();
+        self.set_lasers_shooting_at();
+    }
+
+    /// Applies a single gameplay `Control` (movement or action) to the
+    /// level. Any other control is ignored.
+    pub fn apply(&mut self, control: &Control) {
+        match control {
+            Control::Up | Control::Down | Control::Left | Control::Right => {
+                let dir = match control {
+                    Control::Up => Direction::UP,
+                    Control::Down => Direction::DOWN,
+                    Control::Left => Direction::LEFT,
+                    _ => Direction::RIGHT,
+                };
+                if self.move_player(dir) {
+                    self.stats.moves += 1;
+                }
+            }
+            Control::Action => {
+                self.player_action();
+                self.stats.actions += 1;
+            }
+            Control::ToggleAt(row, col) => {
+                self.toggle_at((*row, *col));
+                self.stats.actions += 1;
+            }
+            _ => (),
+        }
+    }
+
+    /// Applies a `Control` the way it is applied while playing: same as
+    /// `Level::apply` but steps that change the level can be taken back
+    /// with `Control::Undo` and `Control::Restart` puts the level back
+    /// the way it was loaded.
+    pub fn step(&mut self, control: &Control) {
+        match control {
+            Control::Up
+            | Control::Down
+            | Control::Left
+            | Control::Right
+            | Control::Action
+            | Control::ToggleAt(_, _)
+            | Control::Undo
+            | Control::Restart => self.recording.push(*control),
+            _ => return,
+        }
+        match control {
+            Control::Undo => {
+                if let Some((snapshot, moves, actions)) = self.history.pop() {
+                    self.restore(&snapshot);
+                    self.stats.moves = moves;
+                    self.stats.actions = actions;
+                    self.stats.undos += 1;
+                }
+                return;
+            }
+            Control::Restart => {
+                let initial = self.initial.clone();
+                self.restore(&initial);
+                self.history.clear();
+                self.stats = LevelStats {
+                    restarts: self.stats.restarts + 1,
+                    ..LevelStats::new()
+                };
+                return;
+            }
+            _ => (),
+        }
+        let before = self.snapshot();
+        let (moves, actions) = (self.stats.moves, self.stats.actions);
+        self.apply(control);
+        if self.snapshot() != before {
+            self.history.push((before, moves, actions));
+        }
+    }
+
+    /// Returns the current state of the level so it can be resumed later
+    /// with `Level::resume`.
+    pub fn save(&self) -> SavedLevel {
+        SavedLevel {
+            source: self.info.source.key(),
+            snapshot: self.snapshot(),
+            moves: self.stats.moves,
+            actions: self.stats.actions,
+            restarts: self.stats.restarts,
+            undos: self.stats.undos,
+            elapsed: self.stats.started_at.elapsed().as_secs(),
+            controls: self.recording.clone(),
+        }
+    }
+
+    /// Restores a level saved with `Level::save`. Returns `false`, leaving
+    /// the level untouched, if the save doesn't match this level.
+    pub fn resume(&mut self, saved: &SavedLevel) -> bool {
+        if saved.source != self.info.source.key() || saved.snapshot.len() != self.nodes.len() {
+            return false;
+        }
+        self.restore(&saved.snapshot);
+        self.update();
+        self.stats = LevelStats {
+            moves: saved.moves,
+            actions: saved.actions,
+            restarts: saved.restarts,
+            undos: saved.undos,
+            started_at: Instant::now()
+                .checked_sub(Duration::from_secs(saved.elapsed))
+                .unwrap_or_else(Instant::now),
+        };
+        self.recording = saved.controls.clone();
+        true
+    }
+
+    /// Returns the hash of the level's grid, used to check that replays
+    /// are played back on the same level they were recorded on.
+    pub fn hash(&self) -> String {
+        format!("{:016x}", self.hash)
+    }
+
+    /// Returns a replay of every control applied so far.
+    pub fn replay(&self, has_won: bool) -> Replay {
+        Replay {
+            source: self.info.source.clone(),
+            name: self.info.name.clone(),
+            hash: self.hash(),
+            controls: self.recording.clone(),
+            has_won,
+        }
+    }
+
+    /// Plays back `controls` on screen, waiting `delay` between each one.
+    /// Pressing the quit key stops the replay early.
+    pub fn play_replay(&mut self, controls: &[Control], delay: Duration) -> LevelResult {
+        let mut controls = controls.iter();
+        loop {
+            let previous = self.clone();
+            self.update();
+            animation::sweep_beams(self, &previous);
+            self.draw().ok();
+            let state = self.play_state();
+            if !state.is_playing {
+                match &state.reason_for_loss {
+                    Some(reason) => animation::loss(self, reason),
+                    None => animation::win(self),
+                }
+                return self.result(state.has_won, state.reason_for_loss, None);
+            }
+            if let Some(Control::Quit) = Control::poll_input(delay) {
+                return self.result(false, Some(LevelLossReason::Quit), None);
+            }
+            match controls.next() {
+                Some(control) => self.step(control),
+                None => return self.result(false, None, None),
+            }
+        }
+    }
+
+    fn result(
+        &self,
+        has_won: bool,
+        reason_for_loss: Option<LevelLossReason>,
+        saved: Option<SavedLevel>,
+    ) -> LevelResult {
+        let par = if has_won { self.par() } else { None };
+        let score = self.stats.moves + self.stats.actions + self.stats.undos;
+        LevelResult {
+            has_won,
+            reason_for_loss,
+            moves: self.stats.moves,
+            actions: self.stats.actions,
+            restarts: self.stats.restarts,
+            undos: self.stats.undos,
+            time: self.stats.started_at.elapsed(),
+            par,
+            stars: par.map(|par| LevelResult::rate(score, par)).unwrap_or(0),
+            saved,
+            replay: self.replay(has_won),
+        }
+    }
+
+    /// Returns the level's par, either from its metadata or, if it doesn't
+    /// have one, the length of the shortest solution from the start of
+    /// the level. The solution is only searched for once per level.
+    pub fn par(&self) -> Option<usize> {
+        if let Some(par) = self.info.metadata.par {
+            return Some(par);
+        }
+        *self.par_lock().0.get_or_init(|| {
+            let mut level = self.clone();
+            level.restore(&self.initial);
+            solver::solve(&level, solver::DEFAULT_STATE_LIMIT).map(|s| s.len())
+        })
+    }
+
+    /// Returns where the par of the level's grid is kept once it is found,
+    /// and whether nothing had asked for it before.
+    fn par_lock(&self) -> (Arc<OnceLock<Option<usize>>>, bool) {
+        match PARS.write() {
+            Ok(mut pars) => {
+                let new = !pars.contains_key(&self.hash);
+                (pars.entry(self.hash).or_default().clone(), new)
+            }
+            Err(_) => (Arc::new(OnceLock::new()), true),
+        }
+    }
+
+    /// Returns whether the level's par is known without searching for it.
+    fn has_par(&self) -> bool {
+        self.info.metadata.par.is_some() || self.par_lock().0.get().is_some()
+    }
+
+    /// Returns a message suggesting the next step of the shortest solution
+    /// from the current state.
+    fn hint(&self) -> String {
+        match solver::solve(self, solver::DEFAULT_STATE_LIMIT) {
+            Some(solution) => match solution.first() {
+                Some(Control::Action) => "Hint: Toggle your surrounding blocks".to_string(),
+                Some(control) => format!("Hint: {}", control.description()),
+                None => "You've already solved it!".to_string(),
+            },
+            None => "No solution from here, try undoing or restarting".to_string(),
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.nodes
+            .iter()
+            .map(|n| (n.row, n.col, n.state()))
+            .collect()
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        for (node, &(row, col, state)) in self.nodes.iter_mut().zip(snapshot.iter()) {
+            node.row = row;
+            node.col = col;
+            node.set_state(state);
+        }
+    }
+
+    pub(crate) fn play_state(&self) -> PlayState {
         let mut all_statues_lit = true;
         for i in 0..self.nodes.len() {
             match &self.nodes[i].node_type {
@@ -335,23 +974,19 @@ IIIIIIIIIIIII",
                         all_statues_lit = all_statues_lit && s.lit;
                     }
                 }
-                NodeType::Zapper(z) => {
-                    if z.lit {
-                        return PlayState {
-                            is_playing: false,
-                            has_won: false,
-                            reason_for_loss: Some(LevelLossReason::Zapper),
-                        };
-                    }
+                NodeType::Zapper(z) if z.lit => {
+                    return PlayState {
+                        is_playing: false,
+                        has_won: false,
+                        reason_for_loss: Some(LevelLossReason::Zapper),
+                    };
                 }
-                NodeType::Player(p) => {
-                    if p.dead {
-                        return PlayState {
-                            is_playing: false,
-                            has_won: false,
-                            reason_for_loss: Some(LevelLossReason::Death),
-                        };
-                    }
+                NodeType::Player(p) if p.dead => {
+                    return PlayState {
+                        is_playing: false,
+                        has_won: false,
+                        reason_for_loss: Some(LevelLossReason::Death),
+                    };
                 }
                 _ => (),
             }
@@ -370,8 +1005,9 @@ IIIIIIIIIIIII",
         }
     }
 
-    pub fn available_levels(level_dir: &Path) -> Result<Vec<LevelInfo>, String> {
-        let files = match fs::read_dir(level_dir) {
+    /// Returns the paths in `dir` for which `keep` is true, human sorted.
+    fn sorted_entries(dir: &Path, keep: fn(&Path) -> bool) -> Result<Vec<PathBuf>, String> {
+        let files = match fs::read_dir(dir) {
             Ok(f) => f,
             Err(e) => return Err(e.to_string()),
         };
@@ -381,26 +1017,40 @@ IIIIIIIIIIIII",
                 Ok(f) => f,
                 Err(e) => return Err(e.to_string()),
             };
-            filenames.push(f.path().to_path_buf());
+            if keep(&f.path()) {
+                filenames.push(f.path().to_path_buf());
+            }
         }
-        let mut filenames: Vec<_> = filenames
-            .iter()
-            .map(|s| (s.as_path(), s.to_string_lossy()))
-            .collect();
-        filenames.sort_by(|(_, s1), (_, s2)| human_sort::compare(s1, s2));
+        filenames
+            .sort_by(|p1, p2| human_sort::compare(&p1.to_string_lossy(), &p2.to_string_lossy()));
+        Ok(filenames)
+    }
+
+    /// Returns the subdirectories of `level_dir`, leaving out hidden ones.
+    pub fn available_dirs(level_dir: &Path) -> Result<Vec<PathBuf>, String> {
+        Level::sorted_entries(level_dir, |p| {
+            p.is_dir()
+                && !p
+                    .file_name()
+                    .map(|n| n.to_string_lossy().starts_with('.'))
+                    .unwrap_or(false)
+        })
+    }
+
+    /// Returns the info of every `.l1t` file in `level_dir`.
+    pub fn available_levels(level_dir: &Path) -> Result<Vec<LevelInfo>, String> {
+        let filenames = Level::sorted_entries(level_dir, |p| {
+            p.is_file() && p.extension().map(|e| e == "l1t").unwrap_or(false)
+        })?;
         let mut levels = Vec::<LevelInfo>::new();
-        for (f, _) in filenames {
+        for f in filenames.iter() {
             let content = match fs::read_to_string(f) {
                 Ok(c) => c,
                 Err(e) => return Err(e.to_string()),
             };
             let lines: Vec<&str> = content.split('\n').collect();
-            levels.push(LevelInfo {
-                source: LevelSource::File(f.to_path_buf()),
-                name: lines[0].to_string(),
-                author: lines[1].to_string(),
-                description: lines[2].to_string(),
-            });
+            let (info, _) = LevelInfo::parse_header(&lines, LevelSource::File(f.to_path_buf()));
+            levels.push(info);
         }
         Ok(levels)
     }
@@ -434,12 +1084,19 @@ IIIIIIIIIIIII",
                 nodes.push(node);
             }
         }
+        let initial = nodes.iter().map(|n| (n.row, n.col, n.state())).collect();
+        let grid: Vec<&str> = content.iter().map(|l| l.trim_end_matches('\r')).collect();
         Ok(Level {
             info,
             nodes,
             rows,
             cols,
             player_index,
+            stats: LevelStats::new(),
+            history: vec![],
+            initial,
+            hash: replay::content_hash(&grid.join("\n")),
+            recording: vec![],
         })
     }
 
@@ -447,31 +1104,75 @@ IIIIIIIIIIIII",
         if content.len() < 3 {
             return Err("Empty level file.");
         }
-        let info = LevelInfo {
-            source,
-            name: content[0].to_string(),
-            author: content[1].to_string(),
-            description: content[2].to_string(),
-        };
-        Level::parse_grid(&content[3..], info)
+        let (info, grid_start) = LevelInfo::parse_header(content, source);
+        Level::parse_grid(&content[grid_start.min(content.len())..], info)
     }
 
+    /// Loads a level file. Files that only contain the grid, like the
+    /// levels served by repositories, are named after the file.
     pub fn file(filename: PathBuf) -> Result<Level, &'static str> {
         let content: String = fs::read_to_string(&filename).unwrap_or_default();
+        if !LevelInfo::has_header(&content) {
+            let info = LevelInfo {
+                name: filename
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string(),
+                source: LevelSource::File(filename),
+                author: String::new(),
+                description: String::new(),
+                metadata: LevelMetadata::default(),
+            };
+            return Level::from_repository(&content, info);
+        }
         let content: Vec<&str> = content.trim().split('\n').collect();
         Level::parse_full(&content, LevelSource::File(filename))
     }
 
-    pub async fn url(info: LevelInfo) -> Result<Level, &'static str> {
+    /// Returns the grid of a level file without its header, as levels are
+    /// served by a repository. Returns an error if the level can't be
+    /// loaded.
+    pub fn file_grid(filename: &Path) -> Result<String, &'static str> {
+        let content = match fs::read_to_string(filename) {
+            Ok(c) => c,
+            Err(_) => return Err("Error reading level file."),
+        };
+        let content: Vec<&str> = content.trim().split('\n').collect();
+        Level::parse_full(&content, LevelSource::File(filename.to_path_buf()))?;
+        let (_, grid_start) = LevelInfo::parse_header(&content, LevelSource::Core(0));
+        Ok(content[grid_start.min(content.len())..].join("\n") + "\n")
+    }
+
+    /// Parses a level served by a repository, which is either just the
+    /// grid or a complete level file. The level's info comes from the
+    /// file's header if it has one, and is `info` otherwise.
+    pub fn from_repository(content: &str, info: LevelInfo) -> Result<Level, &'static str> {
+        let has_header = LevelInfo::has_header(content);
+        let content: Vec<&str> = content.trim().split('\n').collect();
+        if has_header {
+            Level::parse_full(&content, info.source)
+        } else {
+            Level::parse_grid(&content, info)
+        }
+    }
+
+    /// Downloads a level from a repository. The error says why the level
+    /// couldn't be downloaded or loaded.
+    pub async fn url(info: LevelInfo, patience: Patience) -> Result<Level, String> {
         if let LevelSource::Url(url) = &info.source {
-            let content = match Repository::download_from_url(url.to_string()).await {
+            let content = match Repository::download_from_url(url.to_string(), patience).await {
                 Ok(c) => c,
-                Err(_) => return Err("Error downloading level"),
+                Err(e) => return Err(e.to_string()),
             };
-            let content: Vec<&str> = content.trim().split('\n').collect();
-            Level::parse_grid(&content, info)
+            let mut level = match Level::from_repository(&content, info.clone()) {
+                Ok(l) => l,
+                Err(e) => return Err(format!("{url}: {e}")),
+            };
+            level.info = info;
+            Ok(level)
         } else {
-            Err("Level source was not a URL")
+            Err("Level source was not a URL".to_string())
         }
     }
 
@@ -481,39 +1182,161 @@ IIIIIIIIIIIII",
         Level::parse_full(&content, LevelSource::Core(level))
     }
 
+    /// Opens the pause menu and carries out whatever was selected. Returns
+    /// the control to apply afterwards, or the result of the level if the
+    /// player quit.
+    fn pause(&mut self) -> Result<Control, Box<LevelResult>> {
+        let paused_at = Instant::now();
+        let control = loop {
+            let selection = Menu::open(MenuType::PauseMenu).unwrap_or(Selection::Resume);
+            match selection {
+                Selection::Restart => break Control::Restart,
+                Selection::Undo => break Control::Undo,
+                Selection::Hint => {
+                    self.draw().ok();
+                    Menu::open(MenuType::Message(&self.hint()));
+                    break Control::None;
+                }
+                Selection::Help => {
+                    Menu::open(MenuType::HelpMenu);
+                }
+                Selection::Settings => {
+                    Menu::open(MenuType::SettingsMenu);
+                }
+                Selection::SaveAndQuit => {
+                    return Err(Box::new(self.result(
+                        false,
+                        Some(LevelLossReason::Quit),
+                        Some(self.save()),
+                    )));
+                }
+                Selection::Quit => {
+                    if let Some(Selection::Yes) =
+                        Menu::open(MenuType::YesNoSelection("Are you sure you want to quit?"))
+                    {
+                        return Err(Box::new(self.result(
+                            false,
+                            Some(LevelLossReason::Quit),
+                            None,
+                        )));
+                    }
+                }
+                _ => break Control::None,
+            }
+            self.draw().ok();
+        };
+        self.stats.started_at += paused_at.elapsed();
+        Ok(control)
+    }
+
     pub fn play(&mut self) -> Result<LevelResult, &str> {
+        if self.info.metadata.par.is_none() && self.par_lock().1 {
+            // Search for the par while the level is played so the win
+            // screen doesn't have to wait for it.
+            let level = self.clone();
+            thread::spawn(move || level.par());
+        }
+        let mut previous = Some(self.clone());
+        let mut queued: VecDeque<Control> = VecDeque::new();
         loop {
-            self.reset_statues();
-            self.set_lasers_shooting_at();
+            self.update();
+            if let Some(previous) = previous.take() {
+                animation::sweep_beams(self, &previous);
+            }
             self.draw().ok();
             let state = self.play_state();
             if !state.is_playing {
-                return Ok(LevelResult {
-                    has_won: state.has_won,
-                    reason_for_loss: state.reason_for_loss,
-                });
-            }
-            match Control::read_input() {
-                Control::Up => self.move_player(Direction::UP),
-                Control::Down => self.move_player(Direction::DOWN),
-                Control::Left => self.move_player(Direction::LEFT),
-                Control::Right => self.move_player(Direction::RIGHT),
-                Control::Action => self.player_action(),
+                match &state.reason_for_loss {
+                    Some(reason) => animation::loss(self, reason),
+                    None => animation::win(self),
+                }
+                if state.has_won && !self.has_par() {
+                    Menu::show_progress(Some("Computing par..."));
+                    self.par();
+                    Menu::show_progress(None);
+                }
+                return Ok(self.result(state.has_won, state.reason_for_loss, None));
+            }
+            let control = match queued.pop_front() {
+                Some(control) => {
+                    thread::sleep(WALK_TIME);
+                    control
+                }
+                None => loop {
+                    if let Some(control) = Control::poll_input(HUD_REFRESH) {
+                        break control;
+                    }
+                    self.draw().ok();
+                },
+            };
+            match control {
                 Control::Help => {
                     Menu::open(MenuType::HelpMenu);
                 }
-                Control::Quit => {
-                    if let Some(Selection::Yes) =
-                        Menu::open(MenuType::YesNoSelection("Are you sure you want to quit?"))
-                    {
-                        return Ok(LevelResult {
-                            has_won: false,
-                            reason_for_loss: Some(LevelLossReason::Quit),
-                        });
+                Control::Quit => match self.pause() {
+                    Ok(control) => {
+                        queued.clear();
+                        queued.push_back(control);
                     }
+                    Err(result) => return Ok(*result),
+                },
+                Control::ToggleWidth => {
+                    let settings = Settings::current();
+                    Settings::set(Settings {
+                        double_width: !settings.double_width,
+                        ..settings
+                    });
+                }
+                Control::Click(row, col) => {
+                    if let Some(pos) = self.grid_pos(row, col) {
+                        queued.extend(self.click(pos));
+                    }
+                }
+                control => {
+                    if let Control::Restart | Control::Undo = control {
+                        queued.clear();
+                    } else {
+                        previous = Some(self.clone());
+                    }
+                    self.step(&control);
                 }
-                _ => (),
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_level(grid: &str) -> Level {
+        let info = LevelInfo {
+            source: LevelSource::Core(0),
+            name: String::new(),
+            author: String::new(),
+            description: String::new(),
+            metadata: LevelMetadata::default(),
+        };
+        Level::from_repository(grid, info).unwrap()
+    }
+
+    fn mirror_dir(level: &Level, pos: (u16, u16)) -> Direction {
+        match &level.nodes[level.node_index_at(pos).unwrap()].node_type {
+            NodeType::Mirror(m) => m.dir,
+            _ => panic!("no mirror at {pos:?}"),
+        }
+    }
+
+    #[test]
+    fn clicking_a_mirror_next_to_the_player_toggles_only_that_mirror() {
+        let mut level = grid_level("IIIII\nI/X/I\nIIIII\n");
+        let controls = level.click((1, 3));
+        assert_eq!(controls, vec![Control::ToggleAt(1, 3)]);
+        for control in controls.iter() {
+            level.step(control);
+        }
+        assert_eq!(mirror_dir(&level, (1, 1)), Direction::FORWARD);
+        assert_eq!(mirror_dir(&level, (1, 3)), Direction::BACKWARD);
+        assert_eq!(level.recording, vec![Control::ToggleAt(1, 3)]);
+    }
+}
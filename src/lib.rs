@@ -3,10 +3,20 @@
 //! A strategy game about shooting laser beams and lighting up statues.
 //! Checkout out [the repo](https://github.com/alex-laycalvert/l1t) for
 //! more info.
-pub mod direction;
+pub mod animation;
+pub mod cache;
 pub mod controls;
-pub mod repository;
+pub mod direction;
+pub mod keymap;
 pub mod level;
+pub mod list;
 pub mod menu;
 pub mod node;
+pub mod render;
+pub mod renderer;
+pub mod replay;
+pub mod repository;
+pub mod server;
+pub mod settings;
+pub mod solver;
 pub mod userdata;
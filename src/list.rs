@@ -0,0 +1,262 @@
+use crate::{controls::Control, menu::Menu, render::Frame, settings::Settings};
+use crossterm::style::{Color, Stylize};
+
+/// An item in a `List`.
+pub struct ListItem {
+    pub label: String,
+    /// Disabled items are drawn grayed out and can't be selected.
+    pub enabled: bool,
+}
+
+impl ListItem {
+    pub fn new(label: String) -> ListItem {
+        ListItem {
+            label,
+            enabled: true,
+        }
+    }
+}
+
+/// What a `List` did with a control passed to `List::handle`.
+pub enum ListEvent {
+    /// The item at the index was selected with `Enter` or a click.
+    Selected(usize),
+    /// The quit key was pressed.
+    Cancelled,
+    /// The control moved the selection.
+    Moved,
+    /// The control isn't used by lists and is left to the menu.
+    Ignored(Control),
+}
+
+/// A selectable list of items drawn in a bordered box, used by every
+/// menu that lets the player pick from a list. Items are laid out in
+/// one or more columns and scroll when they don't fit. The box has a
+/// title on its top border and an optional status line underneath.
+///
+/// `Up` and `Down` move between rows. `Left` and `Right` move between
+/// columns, or a page at a time if there is only one column.
+pub struct List {
+    pub items: Vec<ListItem>,
+    /// Index of the highlighted item.
+    pub selected: usize,
+    pub title: String,
+    /// Shown under the bottom border, left empty to hide it.
+    pub status: String,
+    /// Shown instead of the items if there are none.
+    pub empty: String,
+    /// Number of items on each row.
+    columns: usize,
+    /// Number of blank rows and columns between items.
+    gaps: (u16, u16),
+    centered: bool,
+    /// The first row of items that is shown.
+    scroll: usize,
+    /// Number of rows of items shown by the last `List::draw`.
+    page_rows: usize,
+    /// Screen row, column and width of each item shown by the last
+    /// `List::draw`, used to find what was clicked.
+    positions: Vec<(usize, u16, u16, u16)>,
+}
+
+impl List {
+    pub fn new(title: &str, items: Vec<ListItem>) -> List {
+        List {
+            items,
+            selected: 0,
+            title: title.to_string(),
+            status: String::new(),
+            empty: String::new(),
+            columns: 1,
+            gaps: (0, 0),
+            centered: false,
+            scroll: 0,
+            page_rows: 1,
+            positions: vec![],
+        }
+    }
+
+    /// Same as `List::new` with every label enabled.
+    pub fn from_labels(title: &str, labels: Vec<String>) -> List {
+        List::new(title, labels.into_iter().map(ListItem::new).collect())
+    }
+
+    /// Lays the items out in a grid with `columns` items on each row,
+    /// separated by `row_gap` blank rows and `col_gap` blank columns.
+    pub fn grid(mut self, columns: usize, row_gap: u16, col_gap: u16) -> List {
+        self.columns = columns.max(1);
+        self.gaps = (row_gap, col_gap);
+        self
+    }
+
+    /// Centers each label in its item instead of aligning it left.
+    pub fn centered(mut self) -> List {
+        self.centered = true;
+        self
+    }
+
+    /// Highlights the item at `index`, or the closest enabled item after
+    /// it.
+    pub fn select(mut self, index: usize) -> List {
+        self.move_to(index as isize, 1);
+        self
+    }
+
+    /// Returns the number of rows needed to show every item, including
+    /// the borders.
+    pub fn height(&self) -> u16 {
+        let rows = self.items.len().div_ceil(self.columns).max(1) as u16;
+        rows * (1 + self.gaps.0) - self.gaps.0 + 2
+    }
+
+    /// Returns the number of columns needed to show items that are
+    /// `item_width` wide, including the borders and a space of padding on
+    /// each side.
+    pub fn width(&self, item_width: u16) -> u16 {
+        let columns = self.columns as u16;
+        columns * item_width + (columns - 1) * self.gaps.1 + 4
+    }
+
+    /// Draws the list in the `rows` by `cols` box with its top left
+    /// corner at `(row, col)`.
+    pub fn draw(&mut self, frame: &mut Frame, (row, col): (u16, u16), (rows, cols): (u16, u16)) {
+        let end_row = row + rows.max(3) - 1;
+        let end_col = col + cols.max(5) - 1;
+        Menu::draw_borders(frame, row, end_row, col, end_col);
+        if !self.title.is_empty() {
+            let title: String = format!(" {} ", self.title)
+                .chars()
+                .take(cols.saturating_sub(2) as usize)
+                .collect();
+            let title_col = col + cols.saturating_sub(title.chars().count() as u16) / 2;
+            let accent = Settings::current().theme.accent();
+            frame.print(row, title_col, &title.on(accent).black().bold());
+        }
+        if !self.status.is_empty() {
+            let status: String = self.status.chars().take(cols as usize).collect();
+            frame.print(end_row + 1, col + 1, &status.bold());
+        }
+        self.positions.clear();
+        if self.items.is_empty() {
+            frame.print(row + 1, col + 2, &self.empty.as_str().italic());
+            return;
+        }
+        let columns = self.columns as u16;
+        let item_width = (cols.saturating_sub(4 + (columns - 1) * self.gaps.1) / columns).max(1);
+        self.page_rows =
+            ((rows.saturating_sub(2) + self.gaps.0) / (1 + self.gaps.0)).max(1) as usize;
+        let selected_row = self.selected / self.columns;
+        if selected_row < self.scroll {
+            self.scroll = selected_row;
+        } else if selected_row >= self.scroll + self.page_rows {
+            self.scroll = selected_row + 1 - self.page_rows;
+        }
+        let total_rows = self.items.len().div_ceil(self.columns);
+        for (i, item) in self
+            .items
+            .iter()
+            .enumerate()
+            .skip(self.scroll * self.columns)
+            .take(self.page_rows * self.columns)
+        {
+            let item_row = row + 1 + ((i / self.columns - self.scroll) as u16) * (1 + self.gaps.0);
+            let item_col = col + 2 + (i % self.columns) as u16 * (item_width + self.gaps.1);
+            let label: String = item.label.chars().take(item_width as usize).collect();
+            let label = if self.centered {
+                format!("{:^w$}", label, w = item_width as usize)
+            } else {
+                format!("{:<w$}", label, w = item_width as usize)
+            };
+            let (fg, bg) = if item.enabled {
+                Menu::item_colors(i == self.selected)
+            } else {
+                (Color::DarkGrey, Color::Reset)
+            };
+            frame.print(item_row, item_col, &label.with(fg).on(bg).bold());
+            self.positions.push((i, item_row, item_col, item_width));
+        }
+        if total_rows > self.page_rows {
+            let position = format!(
+                " {}-{} of {} ",
+                self.scroll + 1,
+                (self.scroll + self.page_rows).min(total_rows),
+                total_rows
+            );
+            let position_col = end_col.saturating_sub(position.len() as u16 + 1);
+            frame.print(end_row, position_col, &position.stylize());
+        }
+    }
+
+    /// Moves the selection to `target`, wrapping around either end of the
+    /// list and skipping disabled items in the direction of `step`.
+    fn move_to(&mut self, target: isize, step: isize) {
+        let len = self.items.len() as isize;
+        if len == 0 {
+            return;
+        }
+        let mut index = target.rem_euclid(len);
+        for _ in 0..len {
+            if self.items[index as usize].enabled {
+                self.selected = index as usize;
+                return;
+            }
+            index = (index + step).rem_euclid(len);
+        }
+    }
+
+    /// Moves the selection by `offset` items. If `clamp` is set it stops
+    /// at the first or last item, skipping disabled items back towards
+    /// the current one, instead of wrapping around.
+    fn move_by(&mut self, offset: isize, clamp: bool) {
+        let target = self.selected as isize + offset;
+        if clamp {
+            let target = target.clamp(0, self.items.len() as isize - 1);
+            self.move_to(target, -offset.signum());
+        } else {
+            self.move_to(target, offset.signum());
+        }
+    }
+
+    /// Handles a control read while the list is open.
+    pub fn handle(&mut self, control: Control) -> ListEvent {
+        if self.items.is_empty() {
+            return match control {
+                Control::Quit => ListEvent::Cancelled,
+                control => ListEvent::Ignored(control),
+            };
+        }
+        let columns = self.columns as isize;
+        let page = (self.page_rows * self.columns) as isize;
+        match control {
+            Control::Up => self.move_by(-columns, false),
+            Control::Down => self.move_by(columns, false),
+            Control::Left if columns > 1 => self.move_by(-1, false),
+            Control::Right if columns > 1 => self.move_by(1, false),
+            Control::Left => self.move_by(-page, true),
+            Control::Right => self.move_by(page, true),
+            Control::ScrollUp => self.move_by(-1, true),
+            Control::ScrollDown => self.move_by(1, true),
+            Control::GotoTop => self.move_to(0, 1),
+            Control::GotoBottom => self.move_to(-1, -1),
+            Control::Select if self.items[self.selected].enabled => {
+                return ListEvent::Selected(self.selected)
+            }
+            Control::Click(row, col) => {
+                return match self
+                    .positions
+                    .iter()
+                    .find(|&&(_, r, c, w)| r == row && col >= c && col < c + w)
+                {
+                    Some(&(i, _, _, _)) if self.items[i].enabled => {
+                        self.selected = i;
+                        ListEvent::Selected(i)
+                    }
+                    _ => ListEvent::Ignored(control),
+                };
+            }
+            Control::Quit => return ListEvent::Cancelled,
+            control => return ListEvent::Ignored(control),
+        }
+        ListEvent::Moved
+    }
+}
@@ -1,22 +1,30 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use crossterm::{
-    cursor, execute,
+    cursor,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
     terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
 };
 use home::home_dir;
+use l1t::cache::{self, Patience};
+use l1t::keymap::*;
 use l1t::level::*;
 use l1t::menu::*;
-//use l1t::repository::*;
+use l1t::render::*;
+use l1t::replay::*;
+use l1t::repository::*;
+use l1t::server;
+use l1t::settings::*;
 use l1t::userdata::*;
+use serde::Serialize;
 use std::{
     error::Error,
+    fs,
     io::stdout,
     path::{Path, PathBuf},
     thread, time,
 };
 
-const SLEEP_TIME: u64 = 500;
-
 /// A terminal based strategy game about shooting lasers and lighting statues
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -24,11 +32,90 @@ struct Args {
     /// The `.l1t` file to load a level from
     #[arg(short, long)]
     file: Option<PathBuf>,
+    /// The directory browsed by `Local` in the main menu [default: $HOME/.l1t/levels]
+    #[arg(long)]
+    levels_dir: Option<PathBuf>,
+    /// Disable laser beam and win/loss animations
+    #[arg(long)]
+    no_animations: bool,
+    /// Draw every grid cell two columns wide so levels look square
+    #[arg(long, global = true)]
+    double_width: bool,
     ///// Repository to download levels from
     //#[arg(short, long)]
     //repo_url: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Render a static snapshot of a `.l1t` level file
+    Render {
+        /// The `.l1t` file to render
+        file: PathBuf,
+        /// The output format
+        #[arg(long, value_enum, default_value_t = RenderFormat::Ansi)]
+        format: RenderFormat,
+        /// The state of the level to render
+        #[arg(long, value_enum, default_value_t = RenderState::Initial)]
+        state: RenderState,
+        /// Write the render to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Add, remove or list the repositories levels are downloaded from
+    Repo {
+        #[command(subcommand)]
+        command: RepoCommand,
+    },
+    /// Play back a replay file recorded when a level was won or lost
+    Replay {
+        /// The `.l1t_replay` file to play
+        file: PathBuf,
+        /// Number of steps played back per second
+        #[arg(long, default_value_t = 5.0)]
+        speed: f64,
+        /// Check that the replay still wins its level without drawing it
+        #[arg(long)]
+        verify: bool,
+    },
+    /// Serve a directory of `.l1t` files as a repository
+    Serve {
+        /// The directory containing the level files
+        dir: PathBuf,
+        /// The port to listen on
+        #[arg(long, default_value_t = 8000)]
+        port: u16,
+    },
 }
 
+#[derive(Subcommand, Debug)]
+enum RepoCommand {
+    /// Add a repository to the end of the list
+    Add {
+        /// The name shown for the repository
+        name: String,
+        /// The URL the repository is hosted at
+        url: String,
+    },
+    /// Remove a repository
+    Remove {
+        /// The name of the repository
+        name: String,
+    },
+    /// List every repository
+    List,
+    /// Check every level file in a repository directory and write its `l1t` listing
+    Build {
+        /// The repository directory
+        dir: PathBuf,
+    },
+}
+
+/// The most warnings about a repository's listing shown at once.
+const MAX_WARNINGS: usize = 5;
+
 enum PlayStatus<'a> {
     WonLevel,
     Quit,
@@ -38,13 +125,14 @@ enum PlayStatus<'a> {
 
 fn setup() -> crossterm::Result<()> {
     enable_raw_mode()?;
-    execute!(stdout(), cursor::Hide)
+    execute!(stdout(), cursor::Hide, EnableMouseCapture)
 }
 
 fn exit(error: Option<&str>) -> Result<(), Box<dyn Error>> {
     disable_raw_mode()?;
     execute!(
         stdout(),
+        DisableMouseCapture,
         cursor::Show,
         cursor::MoveTo(0, 0),
         Clear(ClearType::All)
@@ -65,12 +153,28 @@ async fn main() -> Result<(), Box<dyn Error>> {
     //.ok();
 
     let args = Args::parse();
+    apply_flags(&args);
+    if let Some(command) = args.command {
+        return run_command(command).await;
+    }
     setup().ok();
+    cache::on_progress(Menu::show_progress);
+    let home = home_dir();
+    if let Some(home) = &home {
+        match Keymap::read(home.to_str().unwrap_or("").to_string()) {
+            Ok(k) => Keymap::set(k),
+            Err(e) => return exit(Some(&e)),
+        }
+    }
     if let Some(filename) = &args.file {
-        return play_file(filename);
+        let user_data = home
+            .as_ref()
+            .and_then(|h| UserData::read(h.to_str().unwrap_or("").to_string()).ok());
+        apply_flags(&args);
+        return play_file(filename, user_data);
     }
 
-    let home = match home_dir() {
+    let home = match home {
         Some(h) => h,
         None => return exit(Some("failed to find user's home directory")),
     };
@@ -79,14 +183,163 @@ async fn main() -> Result<(), Box<dyn Error>> {
         Ok(d) => d,
         Err(e) => return exit(Some(&e)),
     };
+    apply_flags(&args);
+
+    let levels_dir = args
+        .levels_dir
+        .unwrap_or_else(|| PathBuf::from(home.to_string() + "/.l1t/levels"));
+    play(user_data, levels_dir).await
+}
+
+/// Writes the `l1t` listing of the repository in `dir`, built from its
+/// level files.
+fn build_repository(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let listing = match Repository::build_listing(dir) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("{e}");
+            return Err("the listing was not written".into());
+        }
+    };
+    let mut content: Vec<u8> = vec![];
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut content, formatter);
+    listing.serialize(&mut serializer)?;
+    content.push(b'\n');
+    let file = dir.join("l1t");
+    fs::write(&file, content)?;
+    println!(
+        "{}: listed {} levels",
+        file.to_string_lossy(),
+        listing.levels.len()
+    );
+    Ok(())
+}
 
-    play(user_data).await
+/// Applies `--no-animations` and `--double-width` on top of the current
+/// settings so that they take priority over the config file.
+fn apply_flags(args: &Args) {
+    let settings = Settings::current();
+    Settings::set(Settings {
+        animations: settings.animations && !args.no_animations,
+        double_width: settings.double_width || args.double_width,
+        ..settings
+    });
 }
 
-async fn play(mut user_data: UserData) -> Result<(), Box<dyn Error>> {
+async fn run_command(command: Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Render {
+            file,
+            format,
+            state,
+            output,
+        } => {
+            let level = Level::file(file)?;
+            let content = render(&level, format, state)?;
+            match output {
+                Some(output) => fs::write(output, content)?,
+                None => print!("{content}"),
+            }
+        }
+        Command::Repo { command } => {
+            let user_data = || -> Result<UserData, Box<dyn Error>> {
+                let home = home_dir().ok_or("failed to find user's home directory")?;
+                Ok(UserData::read(home.to_string_lossy().to_string())?)
+            };
+            match command {
+                RepoCommand::Add { name, url } => user_data()?.add_repository(name, url)?,
+                RepoCommand::Remove { name } => {
+                    let mut user_data = user_data()?;
+                    match user_data.find_repository(&name) {
+                        Some(i) => user_data.remove_repository(i)?,
+                        None => return Err(format!("no repository named `{name}`").into()),
+                    }
+                }
+                RepoCommand::Build { dir } => build_repository(&dir)?,
+                RepoCommand::List => {
+                    let user_data = user_data()?;
+                    let name_len = user_data
+                        .repositories
+                        .iter()
+                        .map(|r| r.name.chars().count())
+                        .max()
+                        .unwrap_or(0);
+                    for repo in user_data.repositories.iter() {
+                        println!("{: <name_len$}  {}", repo.name, repo.url);
+                    }
+                }
+            }
+        }
+        Command::Replay {
+            file,
+            speed,
+            verify,
+        } => {
+            let replay = Replay::read(&file)?;
+            let mut level = replay_level(&replay).await?;
+            if verify {
+                if !replay.verify(&mut level)? {
+                    return Err(format!("replay does not win `{}`", level.info.name).into());
+                }
+                println!(
+                    "{}: wins `{}` in {} steps",
+                    file.to_string_lossy(),
+                    level.info.name,
+                    replay.controls.len()
+                );
+                return Ok(());
+            }
+            replay.check(&level)?;
+            if speed <= 0.0 {
+                return Err("--speed must be greater than 0".into());
+            }
+            setup().ok();
+            let delay = time::Duration::from_secs_f64(1.0 / speed);
+            let result = level.play_replay(&replay.controls, delay);
+            if !matches!(result.reason_for_loss, Some(LevelLossReason::Quit)) {
+                thread::sleep(time::Duration::from_millis(Settings::current().win_delay));
+                Menu::open(MenuType::Message(if result.has_won {
+                    "Replay finished, the level was won!"
+                } else {
+                    "Replay finished, the level was not won."
+                }));
+            }
+            return exit(None);
+        }
+        Command::Serve { dir, port } => server::serve(dir, port).await?,
+    }
+    Ok(())
+}
+
+/// Loads the level a replay was recorded on.
+async fn replay_level(replay: &Replay) -> Result<Level, Box<dyn Error>> {
+    Ok(match &replay.source {
+        LevelSource::Core(level) => Level::core(*level)?,
+        LevelSource::File(path) => Level::file(path.to_path_buf())?,
+        LevelSource::Url(_) => {
+            Level::url(
+                LevelInfo {
+                    source: replay.source.clone(),
+                    name: replay.name.clone(),
+                    author: String::new(),
+                    description: String::new(),
+                    metadata: LevelMetadata::default(),
+                },
+                Patience::Full,
+            )
+            .await?
+        }
+    })
+}
+
+async fn play(mut user_data: UserData, levels_dir: PathBuf) -> Result<(), Box<dyn Error>> {
     loop {
-        let selection = Menu::open(MenuType::MainSelection(&user_data.completed_core_levels))
-            .unwrap_or(Selection::Play(LevelSource::Core(0)));
+        let selection = Menu::open(MenuType::MainSelection(
+            &user_data.completed_core_levels,
+            &user_data.scores,
+        ))
+        .unwrap_or(Selection::Play(LevelSource::Core(0)));
         match selection {
             Selection::Play(level_source) => match level_source {
                 LevelSource::Core(level) => {
@@ -102,10 +355,10 @@ async fn play(mut user_data: UserData) -> Result<(), Box<dyn Error>> {
                             Ok(l) => l,
                             Err(e) => return exit(Some(e)),
                         };
-                        let result = level.play();
+                        let result = play_level(&mut level, Some(&mut user_data));
                         match handle_level_result(result) {
                             PlayStatus::WonLevel => {
-                                if let Err(e) = user_data.complete(level.info) {
+                                if let Err(e) = user_data.complete(&level) {
                                     return exit(Some(&e));
                                 };
                                 current_level += 1;
@@ -120,36 +373,65 @@ async fn play(mut user_data: UserData) -> Result<(), Box<dyn Error>> {
                 LevelSource::Url(_url) => {}
             },
             Selection::Repository => {
-                if user_data.repositories.is_empty() {
-                    Menu::open(MenuType::Message(
-                        "You don't have any repositories setup in $HOME/.l1t/repositories.l1t_conf",
-                    ));
-                    continue;
-                }
-                while let Some(Selection::Item(i)) =
-                    Menu::open(MenuType::RepositorySelection(&user_data.repositories))
-                {
-                    if let Err(e) = user_data.repositories[i].download_listing().await {
+                let mut selected = 0;
+                loop {
+                    let i = match Menu::open(MenuType::RepositorySelection(
+                        &user_data.repositories,
+                        selected,
+                    )) {
+                        Some(Selection::Item(i)) => i,
+                        Some(Selection::Add) => {
+                            selected = add_repository(&mut user_data);
+                            continue;
+                        }
+                        Some(Selection::Edit(i)) => {
+                            selected = edit_repository(&mut user_data, i);
+                            continue;
+                        }
+                        _ => break,
+                    };
+                    selected = i;
+                    let repo = &mut user_data.repositories[i];
+                    if let Err(e) = repo.download_listing().await {
                         Menu::open(MenuType::Message(&e.to_string()));
                         continue;
                     };
+                    if !repo.warnings.is_empty() {
+                        let mut message = format!(
+                            "Skipped {} invalid levels in {}:\n{}",
+                            repo.warnings.len(),
+                            repo.name,
+                            repo.warnings[..repo.warnings.len().min(MAX_WARNINGS)].join("\n")
+                        );
+                        if repo.warnings.len() > MAX_WARNINGS {
+                            message +=
+                                &format!("\n...and {} more", repo.warnings.len() - MAX_WARNINGS);
+                        }
+                        Menu::open(MenuType::Message(&message));
+                    }
                     loop {
                         if let Some(selection) = Menu::open(MenuType::RepositoryLevelSelection(
                             user_data.repositories[i].clone(),
                             &user_data.completed_levels,
+                            &user_data.scores,
                         )) {
                             match selection {
                                 Selection::Item(j) => {
-                                    let level_info = &user_data.repositories[i].levels[j];
+                                    let level_info = user_data.repositories[i].levels[j].clone();
                                     loop {
-                                        let mut level = match Level::url(level_info.clone()).await {
+                                        let download =
+                                            Level::url(level_info.clone(), Patience::Full);
+                                        let mut level = match download.await {
                                             Ok(l) => l,
-                                            Err(e) => return exit(Some(e)),
+                                            Err(e) => {
+                                                Menu::open(MenuType::Message(&e));
+                                                break;
+                                            }
                                         };
-                                        let result = level.play();
+                                        let result = play_level(&mut level, Some(&mut user_data));
                                         match handle_level_result(result) {
                                             PlayStatus::WonLevel => {
-                                                if let Err(e) = user_data.complete(level.info) {
+                                                if let Err(e) = user_data.complete(&level) {
                                                     return exit(Some(&e));
                                                 };
                                                 break;
@@ -167,6 +449,17 @@ async fn play(mut user_data: UserData) -> Result<(), Box<dyn Error>> {
                     }
                 }
             }
+            Selection::Local => {
+                if let Err(e) = play_local(&mut user_data, &levels_dir) {
+                    return exit(Some(&e));
+                }
+            }
+            Selection::Statistics => {
+                Menu::open(MenuType::Statistics(&user_data));
+            }
+            Selection::Settings => {
+                Menu::open(MenuType::SettingsMenu);
+            }
             Selection::Help => {
                 Menu::open(MenuType::HelpMenu);
             }
@@ -176,15 +469,118 @@ async fn play(mut user_data: UserData) -> Result<(), Box<dyn Error>> {
     exit(None)
 }
 
-fn play_file(filename: &Path) -> Result<(), Box<dyn Error>> {
+/// Lists the entries of `dir` for the `LocalLevelSelection`, including
+/// its parent unless `dir` is the `root` being browsed.
+fn local_entries(root: &Path, dir: &Path, user_data: &UserData) -> Result<Vec<LocalEntry>, String> {
+    let mut entries: Vec<LocalEntry> = vec![];
+    if dir != root {
+        if let Some(parent) = dir.parent() {
+            entries.push(LocalEntry::Parent(parent.to_path_buf()));
+        }
+    }
+    for subdir in Level::available_dirs(dir)? {
+        entries.push(LocalEntry::Directory(subdir));
+    }
+    for info in Level::available_levels(dir)? {
+        if let LevelSource::File(path) = info.source {
+            match Level::file(path.to_path_buf()) {
+                Ok(mut level) => {
+                    level.update();
+                    let completed = user_data.is_file_completed(&level);
+                    entries.push(LocalEntry::Level(Box::new(level), completed));
+                }
+                Err(e) => entries.push(LocalEntry::Broken(path, e.to_string())),
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Browses the level files under `root`, creating it if it doesn't exist.
+/// Returns an error only if playing a level fails.
+fn play_local(user_data: &mut UserData, root: &Path) -> Result<(), String> {
+    if let Err(e) = fs::create_dir_all(root) {
+        Menu::open(MenuType::Message(&format!(
+            "{}: {e}",
+            root.to_string_lossy()
+        )));
+        return Ok(());
+    }
+    let mut dir = root.to_path_buf();
+    loop {
+        let entries = match local_entries(root, &dir, user_data) {
+            Ok(e) => e,
+            Err(e) => {
+                Menu::open(MenuType::Message(&format!(
+                    "{}: {e}",
+                    dir.to_string_lossy()
+                )));
+                return Ok(());
+            }
+        };
+        let i = match Menu::open(MenuType::LocalLevelSelection(
+            &dir,
+            &entries,
+            &user_data.scores,
+        )) {
+            Some(Selection::Item(i)) => i,
+            _ => return Ok(()),
+        };
+        let path = match &entries[i] {
+            LocalEntry::Parent(path) | LocalEntry::Directory(path) => {
+                dir = path.to_path_buf();
+                continue;
+            }
+            LocalEntry::Level(level, _) => match &level.info.source {
+                LevelSource::File(path) => path.to_path_buf(),
+                _ => continue,
+            },
+            LocalEntry::Broken(_, _) => continue,
+        };
+        loop {
+            let mut level = match Level::file(path.to_path_buf()) {
+                Ok(l) => l,
+                Err(e) => {
+                    Menu::open(MenuType::Message(&format!(
+                        "{}: {e}",
+                        path.to_string_lossy()
+                    )));
+                    break;
+                }
+            };
+            let result = play_level(&mut level, Some(user_data));
+            match handle_level_result(result) {
+                PlayStatus::WonLevel => {
+                    if let Err(e) = user_data.complete(&level) {
+                        Menu::open(MenuType::Message(&e));
+                    }
+                    break;
+                }
+                PlayStatus::LostLevel => continue,
+                PlayStatus::Quit => break,
+                PlayStatus::Error(e) => return Err(e.to_string()),
+            }
+        }
+    }
+}
+
+fn play_file(filename: &Path, mut user_data: Option<UserData>) -> Result<(), Box<dyn Error>> {
     loop {
         let mut level = match Level::file(filename.to_path_buf()) {
             Ok(l) => l,
             Err(e) => return exit(Some(e)),
         };
-        let result = level.play();
+        let result = play_level(&mut level, user_data.as_mut());
         match handle_level_result(result) {
-            PlayStatus::WonLevel | PlayStatus::Quit => break,
+            PlayStatus::WonLevel => {
+                if let Some(user_data) = &mut user_data {
+                    if let Err(e) = user_data.complete(&level) {
+                        return exit(Some(&e));
+                    }
+                }
+                break;
+            }
+            PlayStatus::Quit => break,
             PlayStatus::LostLevel => continue,
             PlayStatus::Error(e) => return exit(Some(e)),
         }
@@ -192,22 +588,125 @@ fn play_file(filename: &Path) -> Result<(), Box<dyn Error>> {
     exit(None)
 }
 
-fn handle_level_result(result: Result<LevelResult, &str>) -> PlayStatus {
+/// Plays `level`, first resuming the player's saved game for it if there
+/// is one, and stores a new save if they choose `Save & Quit` or the
+/// level's score otherwise. Attempts
+/// that are won or lost are written as replays to `$HOME/.l1t/replays/`.
+fn play_level<'a>(
+    level: &'a mut Level,
+    user_data: Option<&mut UserData>,
+) -> Result<LevelResult, &'a str> {
+    let result = match user_data {
+        Some(user_data) => {
+            if let Some(saved) = user_data.take_saved_level(&level.info.source) {
+                level.resume(&saved);
+            }
+            let result = level.play()?;
+            let saved = match &result.saved {
+                Some(saved) => user_data.save_level(saved.clone()),
+                None => user_data.record(&result),
+            };
+            if let Err(e) = saved {
+                Menu::open(MenuType::Message(&e));
+            }
+            result
+        }
+        None => level.play()?,
+    };
+    if !matches!(result.reason_for_loss, Some(LevelLossReason::Quit)) {
+        if let Some(home) = home_dir() {
+            if let Err(e) = result.replay.save(home.to_string_lossy().to_string()) {
+                Menu::open(MenuType::Message(&e));
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Asks for the name and URL of a new repository and adds it. Returns
+/// the index of the item to select in the `RepositorySelection`.
+fn add_repository(user_data: &mut UserData) -> usize {
+    let index = user_data.repositories.len();
+    let name = match Menu::open(MenuType::TextPrompt("Name of the repository:", "")) {
+        Some(Selection::Text(name)) => name,
+        _ => return index,
+    };
+    let url = match Menu::open(MenuType::TextPrompt("URL of the repository:", "")) {
+        Some(Selection::Text(url)) => url,
+        _ => return index,
+    };
+    if let Err(e) = user_data.add_repository(name, url) {
+        Menu::open(MenuType::Message(&e));
+    }
+    index
+}
+
+/// Lets the player rename, move or remove the repository at `index`.
+/// Returns the index of the item to select in the `RepositorySelection`.
+fn edit_repository(user_data: &mut UserData, index: usize) -> usize {
+    let name = user_data.repositories[index].name.clone();
+    let options = ["Rename", "Move up", "Move down", "Remove", "Cancel"]
+        .iter()
+        .map(|o| o.to_string())
+        .collect();
+    let result = match Menu::open(MenuType::Selection(&name, options)) {
+        Some(Selection::Item(0)) => match Menu::open(MenuType::TextPrompt("New name:", &name)) {
+            Some(Selection::Text(new_name)) => {
+                user_data.rename_repository(index, new_name).map(|_| index)
+            }
+            _ => Ok(index),
+        },
+        Some(Selection::Item(1)) => user_data.move_repository(index, -1),
+        Some(Selection::Item(2)) => user_data.move_repository(index, 1),
+        Some(Selection::Item(3)) => {
+            match Menu::open(MenuType::YesNoSelection(&format!("Remove `{name}`?"))) {
+                Some(Selection::Yes) => user_data
+                    .remove_repository(index)
+                    .map(|_| index.saturating_sub(1)),
+                _ => Ok(index),
+            }
+        }
+        _ => Ok(index),
+    };
+    match result {
+        Ok(i) => i,
+        Err(e) => {
+            Menu::open(MenuType::Message(&e));
+            index
+        }
+    }
+}
+
+fn handle_level_result(result: Result<LevelResult, &str>) -> PlayStatus<'_> {
     match result {
         Ok(result) => {
             if result.has_won {
-                thread::sleep(time::Duration::from_millis(SLEEP_TIME));
-                Menu::open(MenuType::Message("YAY, You Won!"));
+                thread::sleep(time::Duration::from_millis(Settings::current().win_delay));
+                let time = result.time.as_secs();
+                let mut message = format!(
+                    "YAY, You Won!  Moves: {}  Actions: {}  Time: {:02}:{:02}",
+                    result.moves,
+                    result.actions,
+                    time / 60,
+                    time % 60
+                );
+                if result.undos > 0 {
+                    message += &format!("  Undos: {}", result.undos);
+                }
+                if let Some(par) = result.par {
+                    message += &format!("  Par: {par}  {}", star_rating(result.stars));
+                }
+                Menu::open(MenuType::Message(&message));
                 PlayStatus::WonLevel
             } else if let Some(r) = result.reason_for_loss {
                 match r {
                     LevelLossReason::Zapper => {
-                        thread::sleep(time::Duration::from_millis(SLEEP_TIME));
+                        thread::sleep(time::Duration::from_millis(Settings::current().win_delay));
                         Menu::open(MenuType::Message("Uh oh, you lit a zapper!"));
                         PlayStatus::LostLevel
                     }
                     LevelLossReason::Death => {
-                        thread::sleep(time::Duration::from_millis(SLEEP_TIME));
+                        thread::sleep(time::Duration::from_millis(Settings::current().win_delay));
                         Menu::open(MenuType::Message("Uh oh, you got shot by a laser beam!"));
                         PlayStatus::LostLevel
                     }
@@ -1,29 +1,59 @@
 use crate::{
-    controls::Control,
-    level::{Level, LevelSource},
+    cache::{CacheStatus, Patience},
+    controls::{Control, TextInput},
+    keymap::{Key, Keymap},
+    level::{star_rating, Level, LevelInfo, LevelSource},
+    list::{List, ListEvent, ListItem},
+    render::{Cell, Frame},
+    renderer,
     repository::Repository,
-    userdata::CompletedRepoLevel,
+    settings::Settings,
+    userdata::{CompletedRepoLevel, LevelScore, UserData},
 };
-use crossterm::{
-    cursor::MoveTo,
-    execute,
-    style::{
-        Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor,
-        StyledContent, Stylize,
-    },
-    terminal::{size, Clear, ClearType},
+use chrono::TimeZone;
+use crossterm::style::{Color, StyledContent, Stylize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::RwLock,
 };
-use std::io::stdout;
+
+/// What was on screen before a progress message was shown, so that it
+/// can be put back once the message is cleared.
+static PROGRESS_BACKGROUND: RwLock<Option<Frame>> = RwLock::new(None);
 
 #[derive(Clone)]
 pub enum Selection {
     Play(LevelSource),
     Repository,
+    Local,
     Help,
     Quit,
     Yes,
     No,
     Item(usize),
+    Resume,
+    Restart,
+    Undo,
+    Hint,
+    Settings,
+    SaveAndQuit,
+    Statistics,
+    Add,
+    Edit(usize),
+    Text(String),
+}
+
+/// An item listed in the `LocalLevelSelection`.
+pub enum LocalEntry {
+    /// The directory above the one being browsed.
+    Parent(PathBuf),
+    Directory(PathBuf),
+    /// A level loaded from a file and whether the player has completed
+    /// it.
+    Level(Box<Level>, bool),
+    /// A level file that couldn't be loaded and why.
+    Broken(PathBuf, String),
 }
 
 pub enum MenuType<'a> {
@@ -49,32 +79,137 @@ pub enum MenuType<'a> {
     /// the list of chunks to print.
     ScrollableMenu(Vec<Vec<StyledContent<&'a str>>>),
 
-    /// Draws the `Main Menu` of the application with the logo
-    /// and selections for `Play`, `Help`, and `Quit`. Must
+    /// Draws the `Main Menu` of the application with the logo and
+    /// selections for `Play`, `Online`, `Local`, `Stats`, `Settings`,
+    /// `Help`, and `Quit`. Must
     /// provide a `Vec<usize>` representing the core levels the
-    /// player has completed and a `Vec<Repository>` which is
-    /// the list of available repositories the user has.
+    /// player has completed and the player's `LevelScore` on each
+    /// level, by `LevelSource::key`.
     ///
     /// Selecting `Play` will open the `CoreLevelSelection` and
     /// will return a `Selection::Play(l)` where `l` is the selected
     /// level. Selecting `Repository` or `Online` from the menu will
     /// return a `Selection::Play(l)` where `l` is the selected repository
     /// level.
-    MainSelection(&'a Vec<usize>),
+    MainSelection(&'a Vec<usize>, &'a HashMap<String, LevelScore>),
+
+    /// Same as `HelpMenu` but summarizes the player's play time,
+    /// completed levels, moves, deaths and recent completions from
+    /// their `UserData`.
+    Statistics(&'a UserData),
 
     /// Draws the `Core Level` selection menu for the player
     /// to choose one of the built-in levels. Must be provided
     /// a `Vec<usize>` representing the core levels the player
-    /// has completed.
-    CoreLevelSelection(&'a Vec<usize>),
+    /// has completed and the player's `LevelScore` on each level,
+    /// which is shown next to the grid along with a preview of the
+    /// highlighted level.
+    CoreLevelSelection(&'a Vec<usize>, &'a HashMap<String, LevelScore>),
 
     /// Draws the `Repository` selection menu to allow
     /// the player to select which repo they want to play
-    /// a level from. After selecting a repo, the `RepositoryLevelSelection`
+    /// a level from, starting at the repo at the given index.
+    /// After selecting a repo, the `RepositoryLevelSelection`
     /// is opened to select the url of the level.
-    RepositorySelection(&'a Vec<Repository>),
+    ///
+    /// Returns `Selection::Add` if the last item is selected to add a
+    /// repo, or `Selection::Edit(i)` if the action key is pressed on
+    /// the repo at index `i`.
+    RepositorySelection(&'a Vec<Repository>, usize),
+
+    /// Dialog box that asks for a line of text, starting with the given
+    /// text. Returns `Selection::Text` on `Enter` or `None` on `Esc`.
+    TextPrompt(&'a str, &'a str),
+
+    /// Lists the levels of a `Repository`, marking the completed ones
+    /// along with the stars, best move count and best time from the
+    /// player's `LevelScore` on them. The highlighted level is downloaded
+    /// the first time it is highlighted and previewed under the list.
+    /// The list can be searched by name, author and description, filtered
+    /// by completion or tag and sorted. Returns the index of the selected
+    /// level in the repository.
+    RepositoryLevelSelection(
+        Repository,
+        &'a Vec<CompletedRepoLevel>,
+        &'a HashMap<String, LevelScore>,
+    ),
+
+    /// Lists the subdirectories and levels of a local level directory,
+    /// with a preview of the highlighted level. Returns the index of the
+    /// selected `LocalEntry`.
+    LocalLevelSelection(
+        &'a Path,
+        &'a Vec<LocalEntry>,
+        &'a HashMap<String, LevelScore>,
+    ),
+
+    /// Drawn over a level when it is paused. Returns the selected
+    /// `Resume`, `Restart`, `Undo`, `Hint`, `Help`, `Settings`,
+    /// `SaveAndQuit` or `Quit`. Pressing `q` again resumes.
+    PauseMenu,
+
+    /// Lists the current `Settings`, which are changed with `Enter`,
+    /// `Left` and `Right`, applied right away and saved to the config
+    /// file.
+    SettingsMenu,
+
+    /// Lists the keys bound to each control and lets the player bind a
+    /// new key to one. Opened from the `SettingsMenu`.
+    KeyBindings,
+}
+
+/// Which levels are listed in the `RepositoryLevelSelection`.
+enum LevelFilter {
+    All,
+    Uncompleted,
+    Completed,
+    Tag(String),
+}
 
-    RepositoryLevelSelection(Repository, &'a Vec<CompletedRepoLevel>),
+impl LevelFilter {
+    fn name(&self) -> String {
+        match self {
+            LevelFilter::All => "all".to_string(),
+            LevelFilter::Uncompleted => "uncompleted".to_string(),
+            LevelFilter::Completed => "completed".to_string(),
+            LevelFilter::Tag(tag) => format!("#{tag}"),
+        }
+    }
+}
+
+/// How levels are ordered in the `RepositoryLevelSelection`.
+#[derive(Clone, Copy)]
+enum LevelSort {
+    /// The order of the repository's listing.
+    Listing,
+    Name,
+    Author,
+    /// Easiest first.
+    Difficulty,
+    /// Newest first.
+    Date,
+}
+
+impl LevelSort {
+    fn next(self) -> LevelSort {
+        match self {
+            LevelSort::Listing => LevelSort::Name,
+            LevelSort::Name => LevelSort::Author,
+            LevelSort::Author => LevelSort::Difficulty,
+            LevelSort::Difficulty => LevelSort::Date,
+            LevelSort::Date => LevelSort::Listing,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            LevelSort::Listing => "listing",
+            LevelSort::Name => "name",
+            LevelSort::Author => "author",
+            LevelSort::Difficulty => "difficulty",
+            LevelSort::Date => "date",
+        }
+    }
 }
 
 const RED: Color = Color::Rgb { r: 255, g: 0, b: 0 };
@@ -87,156 +222,387 @@ const YELLOW: Color = Color::Rgb {
 pub struct Menu;
 
 impl Menu {
-    fn draw_borders(
+    pub(crate) fn draw_borders(
+        frame: &mut Frame,
         start_row: u16,
         end_row: u16,
         start_col: u16,
         end_col: u16,
-    ) -> crossterm::Result<()> {
+    ) {
+        let cell = |glyph| Cell {
+            glyph,
+            ..Cell::default()
+        };
         for r in start_row..=end_row {
             for c in start_col..=end_col {
                 if r == start_row || r == end_row {
-                    execute!(stdout(), MoveTo(c, r), Print("─"),)?;
+                    frame.set(r, c, cell('─'));
                 } else if c == start_col || c == end_col {
-                    execute!(stdout(), MoveTo(c, r), Print("│"),)?;
+                    frame.set(r, c, cell('│'));
                 } else {
-                    execute!(stdout(), MoveTo(c, r), Print(" "),)?;
+                    frame.set(r, c, cell(' '));
+                }
+            }
+        }
+        frame.set(start_row, start_col, cell('┌'));
+        frame.set(start_row, end_col, cell('┐'));
+        frame.set(end_row, start_col, cell('└'));
+        frame.set(end_row, end_col, cell('┘'));
+    }
+
+    /// Prints each line of styled chunks one after the other starting at
+    /// `(row, col)`.
+    fn print_lines(frame: &mut Frame, row: u16, col: u16, lines: &[Vec<StyledContent<&str>>]) {
+        for (i, line) in lines.iter().enumerate() {
+            let mut c = col;
+            for piece in line.iter() {
+                c = frame.print(row + i as u16, c, piece);
+            }
+        }
+    }
+
+    /// Returns the first key bound to `control`, as shown in menus.
+    fn key_name(control: Control) -> String {
+        match Keymap::current().keys(control).first() {
+            Some(k) => k.to_string(),
+            None => "unbound".to_string(),
+        }
+    }
+
+    /// Returns the colors of a list item depending on whether it is
+    /// the current selection.
+    pub(crate) fn item_colors(selected: bool) -> (Color, Color) {
+        if selected {
+            (Color::Black, Settings::current().theme.accent())
+        } else {
+            (Color::White, Color::Reset)
+        }
+    }
+
+    /// Draws a miniature of `level` in the `rows` by `cols` area with its
+    /// top left corner at `(row, col)`, one column per cell. Levels that
+    /// don't fit are scaled down, keeping the most important cell of each
+    /// block: a node or beam over a wall over an empty space.
+    fn draw_preview(frame: &mut Frame, level: &Level, row: u16, col: u16, rows: u16, cols: u16) {
+        if rows == 0 || cols == 0 {
+            return;
+        }
+        let grid = level.grid_frame(1);
+        let scale = grid
+            .rows
+            .div_ceil(rows)
+            .max(grid.cols.div_ceil(cols))
+            .max(1);
+        let is_wall = |cell: &Cell| cell.glyph == 'I' && cell.bg == Color::White;
+        for r in 0..grid.rows.div_ceil(scale) {
+            for c in 0..grid.cols.div_ceil(scale) {
+                let mut cell = Cell::default();
+                for (br, bc) in (0..scale).flat_map(|br| (0..scale).map(move |bc| (br, bc))) {
+                    let (gr, gc) = (r * scale + br, c * scale + bc);
+                    if gr >= grid.rows || gc >= grid.cols {
+                        continue;
+                    }
+                    let candidate = grid.get(gr, gc);
+                    if candidate == Cell::default() {
+                        continue;
+                    }
+                    if !is_wall(&candidate) {
+                        cell = candidate;
+                        break;
+                    }
+                    cell = candidate;
+                }
+                frame.set(row + r, col + c, cell);
+            }
+        }
+    }
+
+    /// Draws the name, author and description of a level, the player's
+    /// `score` on it if they have one, and a `preview` of it, or why it
+    /// couldn't be loaded, in the `rows` by `cols` area with its top left
+    /// corner at `(row, col)`.
+    fn draw_details(
+        frame: &mut Frame,
+        info: &LevelInfo,
+        preview: Result<&Level, &str>,
+        score: Option<&LevelScore>,
+        (row, col): (u16, u16),
+        (rows, cols): (u16, u16),
+    ) {
+        let text = |s: &str| -> String { s.chars().take(cols as usize).collect() };
+        let title = if info.author.is_empty() {
+            info.name.to_string()
+        } else {
+            format!("{} by {}", info.name, info.author)
+        };
+        let score_lines = score.map(Menu::score_lines).unwrap_or_default();
+        let header_rows = if score.is_some() {
+            4 + score_lines.len() as u16
+        } else {
+            3
+        };
+        let error = match preview {
+            Ok(_) => vec![],
+            Err(e) => Menu::wrap(e, cols as usize),
+        };
+        let preview_rows = match preview {
+            Ok(level) => level.rows.min(rows.saturating_sub(header_rows)),
+            Err(_) => error.len() as u16,
+        };
+        let row = row + rows.saturating_sub(header_rows + preview_rows) / 2;
+        frame.print(row, col, &text(&title).bold());
+        frame.print(row + 1, col, &text(&info.description).italic());
+        if let Some(score) = score {
+            frame.print(row + 2, col, &star_rating(score.stars).with(YELLOW).bold());
+            for (i, line) in score_lines.iter().enumerate() {
+                frame.print(row + 3 + i as u16, col, &text(line).stylize());
+            }
+        }
+        match preview {
+            Ok(level) => Menu::draw_preview(
+                frame,
+                level,
+                row + header_rows,
+                col,
+                rows.saturating_sub(header_rows),
+                cols,
+            ),
+            Err(_) => {
+                for (i, line) in error.iter().enumerate() {
+                    let line = line.as_str().with(Color::Red);
+                    frame.print(row + header_rows + i as u16, col, &line);
                 }
             }
         }
-        execute!(
-            stdout(),
-            MoveTo(start_col, start_row),
-            Print("┌"),
-            MoveTo(end_col, start_row),
-            Print("┐"),
-            MoveTo(start_col, end_row),
-            Print("└"),
-            MoveTo(end_col, end_row),
-            Print("┘"),
-        )
+    }
+
+    /// Downloads a repository level to preview it. The menus aren't async,
+    /// so this blocks on the current runtime, and returns an error when
+    /// not running on a multi-threaded runtime where that is possible.
+    fn fetch_preview(info: &LevelInfo) -> Result<Level, String> {
+        let handle = match tokio::runtime::Handle::try_current() {
+            Ok(h) if h.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => h,
+            _ => return Err("Previews aren't available here".to_string()),
+        };
+        let mut level = tokio::task::block_in_place(|| {
+            handle.block_on(Level::url(info.clone(), Patience::Quick))
+        })?;
+        level.update();
+        Ok(level)
+    }
+
+    /// Splits `text` into lines of at most `width` characters, breaking
+    /// between words where possible and at every newline.
+    fn wrap(text: &str, width: usize) -> Vec<String> {
+        let width = width.max(1);
+        let mut lines: Vec<String> = vec![];
+        for paragraph in text.lines() {
+            let mut line = String::new();
+            for word in paragraph.split(' ') {
+                let mut word = word.to_string();
+                while word.chars().count() > width {
+                    if !line.is_empty() {
+                        lines.push(line);
+                        line = String::new();
+                    }
+                    let rest = word.split_off(word.char_indices().nth(width).map_or(0, |(i, _)| i));
+                    lines.push(word);
+                    word = rest;
+                }
+                let len = line.chars().count();
+                if len > 0 && len + 1 + word.chars().count() > width {
+                    lines.push(line);
+                    line = String::new();
+                }
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line += &word;
+            }
+            lines.push(line);
+        }
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines
+    }
+
+    /// Formats seconds since the epoch as a local date and time.
+    fn date_text(timestamp: u64) -> String {
+        match chrono::Local.timestamp_opt(timestamp as i64, 0) {
+            chrono::LocalResult::Single(d) => d.format("%Y-%m-%d %H:%M").to_string(),
+            _ => "-".to_string(),
+        }
+    }
+
+    /// Shows `message` on the bottom row of the screen, or puts back what
+    /// was there before if there is no message. Used to report progress
+    /// while a download is slow or being retried.
+    pub fn show_progress(message: Option<&str>) {
+        let mut background = match PROGRESS_BACKGROUND.write() {
+            Ok(b) => b,
+            Err(_) => return,
+        };
+        let message = match message {
+            Some(m) => m,
+            None => {
+                if let Some(frame) = background.take() {
+                    renderer::present(&frame).ok();
+                }
+                return;
+            }
+        };
+        let mut frame = background.get_or_insert_with(renderer::last).clone();
+        let (term_cols, term_rows) = renderer::size();
+        let row = term_rows.saturating_sub(1);
+        let text: String = format!("{message:<w$}", w = term_cols as usize)
+            .chars()
+            .take(term_cols as usize)
+            .collect();
+        frame.print(row, 0, &text.italic());
+        renderer::present(&frame).ok();
+    }
+
+    /// Describes where a repository's level listing was loaded from.
+    fn cache_text(status: Option<CacheStatus>) -> String {
+        match status {
+            Some(CacheStatus::Downloaded) => "Online".to_string(),
+            Some(CacheStatus::UpToDate) => "Online, cached".to_string(),
+            Some(CacheStatus::Offline { fetched_at }) => {
+                format!("Offline, cached {}", Menu::date_text(fetched_at))
+            }
+            Some(CacheStatus::Local) => "Local".to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// Formats a number of seconds as `mm:ss`, or `-` for `None`.
+    fn time_text(seconds: Option<u64>) -> String {
+        match seconds {
+            Some(s) => format!("{:02}:{:02}", s / 60, s % 60),
+            None => "-".to_string(),
+        }
+    }
+
+    /// Returns the lines describing `score` shown next to level lists:
+    /// the best win, attempts, deaths by cause and when the level was
+    /// first and last completed.
+    fn score_lines(score: &LevelScore) -> Vec<String> {
+        let mut lines = vec![
+            match score.best_moves {
+                Some(moves) => format!(
+                    "Best: {moves} moves in {}",
+                    Menu::time_text(score.best_time)
+                ),
+                None => "Not completed yet".to_string(),
+            },
+            format!("Attempts: {}  Restarts: {}", score.attempts, score.restarts),
+            format!(
+                "Deaths: {} by zappers, {} by laser beams",
+                score.zapper_deaths, score.laser_deaths
+            ),
+        ];
+        if let Some(first) = score.first_completed_at {
+            lines.push(format!("First completed: {}", Menu::date_text(first)));
+        }
+        if let Some(last) = score.last_completed_at {
+            lines.push(format!("Last completed: {}", Menu::date_text(last)));
+        }
+        lines
     }
 
     pub fn open(menu_type: MenuType) -> Option<Selection> {
         let row_padding = 1;
         let col_padding = 2;
         match menu_type {
-            MenuType::MainSelection(completed_levels) => {
+            MenuType::MainSelection(completed_levels, scores) => {
                 let row_padding = 2;
                 let col_padding = 3;
-                let options: [Selection; 4] = [
+                let options: [Selection; 7] = [
                     Selection::Play(LevelSource::Core(0)),
                     Selection::Repository,
+                    Selection::Local,
+                    Selection::Statistics,
+                    Selection::Settings,
                     Selection::Help,
                     Selection::Quit,
                 ];
                 let mut current_selection = 0;
                 loop {
-                    let (term_cols, term_rows) = size().unwrap_or((0, 0));
+                    let (term_cols, term_rows) = renderer::size();
                     let start_row: u16 =
-                        (term_rows - options.len() as u16 * 2 - 10 - row_padding) / 2 - row_padding;
+                        (term_rows.saturating_sub(options.len() as u16 * 2 + 10 + row_padding) / 2)
+                            .saturating_sub(row_padding);
                     let mut start_col: u16 = (term_cols - 23) / 2 - col_padding;
-                    let end_row: u16 =
-                        (term_rows + options.len() as u16 + 10 + row_padding) / 2 + row_padding;
+                    let end_row: u16 = start_row + row_padding * 2 + options.len() as u16 * 2 + 9;
                     let end_col: u16 = (term_cols + 23) / 2 + col_padding;
-                    if (end_col - start_col) % 2 != 0 {
+                    if !(end_col - start_col).is_multiple_of(2) {
                         start_col -= 1;
                     }
-                    execute!(stdout(), Clear(ClearType::All)).ok();
-                    Menu::draw_borders(start_row, end_row, start_col, end_col).ok();
-                    execute!(
-                        stdout(),
-                        SetAttribute(Attribute::Bold),
-                        MoveTo(start_col + col_padding, start_row + row_padding + 1),
-                        Print("          /"),
-                        SetForegroundColor(RED),
-                        Print("-------"),
-                        SetBackgroundColor(RED),
-                        Print("L"),
-                        SetBackgroundColor(Color::Reset),
-                        MoveTo(start_col + col_padding, start_row + row_padding + 2),
-                        SetForegroundColor(Color::Green),
-                        Print(" ___      "),
-                        SetForegroundColor(RED),
-                        Print("|"),
-                        SetForegroundColor(Color::Green),
-                        Print("__      _"),
-                        MoveTo(start_col + col_padding, start_row + row_padding + 3),
-                        Print("|_  |  "),
-                        SetForegroundColor(RED),
-                        Print("<--"),
-                        SetForegroundColor(Color::White),
-                        Print("/"),
-                        SetForegroundColor(Color::Green),
-                        Print("  |    | \\_"),
-                        MoveTo(start_col + col_padding, start_row + row_padding + 4),
-                        Print("  | |     `| |    | __|"),
-                        MoveTo(start_col + col_padding, start_row + row_padding + 5),
-                        Print("  | |      | |    | |"),
-                        MoveTo(start_col + col_padding, start_row + row_padding + 6),
-                        Print("  | |_    _|_|_   | |_ "),
-                        MoveTo(start_col + col_padding, start_row + row_padding + 7),
-                        SetForegroundColor(RED),
-                        Print("--"),
-                        SetForegroundColor(Color::White),
-                        Print("\\"),
-                        SetForegroundColor(Color::Green),
-                        Print("___\\  |_____| "),
-                        SetForegroundColor(RED),
-                        Print("--"),
-                        SetForegroundColor(Color::White),
-                        Print("\\"),
-                        SetForegroundColor(Color::Green),
-                        Print("__|"),
-                        MoveTo(start_col + col_padding, start_row + row_padding + 8),
-                        SetForegroundColor(RED),
-                        Print("  |                v"),
-                        MoveTo(start_col + col_padding, start_row + row_padding + 9),
-                        Print("  v"),
-                        MoveTo(start_col + col_padding, start_row + row_padding + 10),
-                        Print("  "),
-                        SetForegroundColor(YELLOW),
-                        SetBackgroundColor(YELLOW),
-                        Print("S"),
-                        ResetColor,
-                    )
-                    .ok();
+                    let mut frame = renderer::blank();
+                    Menu::draw_borders(&mut frame, start_row, end_row, start_col, end_col);
+                    Menu::print_lines(
+                        &mut frame,
+                        start_row + row_padding + 1,
+                        start_col + col_padding,
+                        &[
+                            vec![
+                                "          /".bold(),
+                                "-------".with(RED).bold(),
+                                "L".with(RED).on(RED).bold(),
+                            ],
+                            vec![
+                                " ___      ".green().bold(),
+                                "|".with(RED).bold(),
+                                "__      _".green().bold(),
+                            ],
+                            vec![
+                                "|_  |  ".green().bold(),
+                                "<--".with(RED).bold(),
+                                "/".white().bold(),
+                                "  |    | \\_".green().bold(),
+                            ],
+                            vec!["  | |     `| |    | __|".green().bold()],
+                            vec!["  | |      | |    | |".green().bold()],
+                            vec!["  | |_    _|_|_   | |_ ".green().bold()],
+                            vec![
+                                "--".with(RED).bold(),
+                                "\\".white().bold(),
+                                "___\\  |_____| ".green().bold(),
+                                "--".with(RED).bold(),
+                                "\\".white().bold(),
+                                "__|".green().bold(),
+                            ],
+                            vec!["  |                v".with(RED).bold()],
+                            vec!["  v".with(RED).bold()],
+                            vec!["  ".stylize(), "S".with(YELLOW).on(YELLOW).bold()],
+                        ],
+                    );
                     for (i, _) in options.iter().enumerate() {
                         let option = match options[i] {
                             Selection::Play(_) => "P L A Y",
                             Selection::Repository => "O N L I N E",
+                            Selection::Local => "L O C A L",
+                            Selection::Statistics => "S T A T S",
+                            Selection::Settings => "S E T T I N G S",
                             Selection::Help => "H E L P",
                             Selection::Quit => "Q U I T",
                             _ => "",
                         };
-                        execute!(
-                            stdout(),
-                            SetForegroundColor(if i == current_selection {
-                                Color::Black
-                            } else {
-                                Color::White
-                            }),
-                            SetBackgroundColor(if i == current_selection {
-                                Color::White
-                            } else {
-                                Color::Reset
-                            }),
-                            MoveTo(
-                                (term_cols - 23) / 2,
-                                start_row + row_padding * 2 + i as u16 * 2 + 10,
-                            ),
-                            SetAttribute(Attribute::Bold),
-                            Print(format!("{:^23}", option)),
-                            ResetColor,
-                        )
-                        .ok();
+                        let (fg, bg) = Menu::item_colors(i == current_selection);
+                        frame.print(
+                            start_row + row_padding * 2 + i as u16 * 2 + 10,
+                            (term_cols - 23) / 2,
+                            &format!("{:^23}", option).with(fg).on(bg).bold(),
+                        );
                     }
+                    renderer::present(&frame).ok();
                     match Control::read_input() {
                         Control::Select => match options[current_selection] {
                             Selection::Play(_) => {
-                                if let Some(Selection::Item(i)) =
-                                    Menu::open(MenuType::CoreLevelSelection(completed_levels))
-                                {
+                                if let Some(Selection::Item(i)) = Menu::open(
+                                    MenuType::CoreLevelSelection(completed_levels, scores),
+                                ) {
                                     return Some(Selection::Play(LevelSource::Core(i)));
                                 }
                             }
@@ -259,66 +625,58 @@ impl Menu {
                 return Some(options[current_selection].clone());
             }
             MenuType::Message(message) => loop {
-                let (term_cols, term_rows) = size().unwrap_or((0, 0));
-                let start_row: u16 = term_rows / 2 - row_padding - 1;
-                let start_col: u16 =
-                    (term_cols - (term_cols - 4).min(message.len() as u16)) / 2 - col_padding;
-                let end_row: u16 = (term_rows + row_padding) / 2 + row_padding;
-                let end_col: u16 = (term_cols + message.len() as u16) / 2 + col_padding;
-                Menu::draw_borders(start_row, end_row, start_col, end_col).ok();
-                execute!(
-                    stdout(),
-                    MoveTo(start_col + 2, term_rows / 2),
-                    Print(message),
-                )
-                .ok();
-                if let Control::Select = Control::read_input() {
+                let (term_cols, term_rows) = renderer::size();
+                let lines = Menu::wrap(message, term_cols.saturating_sub(8) as usize);
+                let height = lines.len() as u16;
+                let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as u16;
+                let first_row = (term_rows / 2).saturating_sub((height - 1) / 2);
+                let start_row: u16 = first_row.saturating_sub(row_padding + 1);
+                let start_col: u16 = ((term_cols - width) / 2).saturating_sub(col_padding);
+                let end_row: u16 = (term_rows + row_padding) / 2 + row_padding + height / 2;
+                let end_col: u16 = (term_cols + width) / 2 + col_padding;
+                let mut frame = renderer::last();
+                Menu::draw_borders(&mut frame, start_row, end_row, start_col, end_col);
+                for (i, line) in lines.iter().enumerate() {
+                    frame.print(
+                        first_row + i as u16,
+                        start_col + 2,
+                        &line.as_str().stylize(),
+                    );
+                }
+                renderer::present(&frame).ok();
+                if let Control::Select | Control::Click(_, _) = Control::read_input() {
                     break;
                 }
             },
             MenuType::YesNoSelection(message) => {
                 let mut current_selection = Selection::No;
+                let background = renderer::last();
                 loop {
-                    let (term_cols, term_rows) = size().unwrap_or((0, 0));
+                    let (term_cols, term_rows) = renderer::size();
                     let start_row: u16 = term_rows / 2 - row_padding - 1;
                     let start_col: u16 = (term_cols - message.len() as u16) / 2 - col_padding;
                     let end_row: u16 = (term_rows + row_padding) / 2 + row_padding + 2;
                     let end_col: u16 = (term_cols + message.len() as u16) / 2 + col_padding;
-                    Menu::draw_borders(start_row, end_row, start_col, end_col).ok();
-                    execute!(
-                        stdout(),
-                        MoveTo((term_cols - message.len() as u16) / 2, term_rows / 2),
-                        Print(message),
-                    )
-                    .ok();
-                    execute!(
-                        stdout(),
-                        MoveTo(term_cols / 2 - 6, end_row - row_padding - 1),
-                        SetForegroundColor(if matches!(current_selection, Selection::Yes) {
-                            Color::Black
-                        } else {
-                            Color::White
-                        }),
-                        SetBackgroundColor(if matches!(current_selection, Selection::Yes) {
-                            Color::White
-                        } else {
-                            Color::Reset
-                        }),
-                        Print(" YES ".bold()),
-                        MoveTo(term_cols / 2 + 1, end_row - row_padding - 1),
-                        SetForegroundColor(if matches!(current_selection, Selection::No) {
-                            Color::Black
-                        } else {
-                            Color::White
-                        }),
-                        SetBackgroundColor(if matches!(current_selection, Selection::No) {
-                            Color::White
-                        } else {
-                            Color::Reset
-                        }),
-                        Print(" NO ".bold())
-                    )
-                    .ok();
+                    let mut frame = background.clone();
+                    Menu::draw_borders(&mut frame, start_row, end_row, start_col, end_col);
+                    frame.print(
+                        term_rows / 2,
+                        (term_cols - message.len() as u16) / 2,
+                        &message.stylize(),
+                    );
+                    let (fg, bg) = Menu::item_colors(matches!(current_selection, Selection::Yes));
+                    frame.print(
+                        end_row - row_padding - 1,
+                        term_cols / 2 - 6,
+                        &" YES ".with(fg).on(bg).bold(),
+                    );
+                    let (fg, bg) = Menu::item_colors(matches!(current_selection, Selection::No));
+                    frame.print(
+                        end_row - row_padding - 1,
+                        term_cols / 2 + 1,
+                        &" NO ".with(fg).on(bg).bold(),
+                    );
+                    renderer::present(&frame).ok();
                     match Control::read_input() {
                         Control::Left | Control::Right => {
                             if matches!(current_selection, Selection::No) {
@@ -334,7 +692,38 @@ impl Menu {
                 }
             }
             MenuType::HelpMenu => {
-                return Menu::open(MenuType::ScrollableMenu(vec![
+                let keymap = Keymap::current();
+                let bindings: Vec<(String, &str)> = Control::BINDABLE
+                    .iter()
+                    .filter_map(|c| {
+                        let keys: Vec<String> =
+                            keymap.keys(*c).iter().map(|k| k.to_string()).collect();
+                        if keys.is_empty() {
+                            return None;
+                        }
+                        Some((format!(" {} - ", keys.join(", ")), c.description()))
+                    })
+                    .collect();
+                let mut controls: Vec<Vec<StyledContent<&str>>> = bindings
+                    .iter()
+                    .map(|(keys, description)| vec![keys.as_str().bold(), description.stylize()])
+                    .collect();
+                controls.push(vec![]);
+                controls.push(vec![
+                    "Click to walk to an empty space or to toggle ".stylize()
+                ]);
+                controls.push(vec![
+                    "a block next to you. Menu items can be clicked ".stylize()
+                ]);
+                controls.push(vec![
+                    "and menus can be scrolled with the mouse wheel.".stylize()
+                ]);
+                controls.push(vec![]);
+                controls.push(vec![
+                    "Keys can be changed from SETTINGS or in your".stylize()
+                ]);
+                controls.push(vec!["`$HOME/.l1t/keymap.l1t_conf` file.".stylize()]);
+                let mut content = vec![
                     vec![],
                     vec![
                         "In ".stylize(),
@@ -345,19 +734,9 @@ impl Menu {
                     vec![],
                     vec!["CONTROLS".bold().underlined()],
                     vec![],
-                    vec![" W - ".bold(), "Move Up".stylize()],
-                    vec![" S - ".bold(), "Move Down".stylize()],
-                    vec![" A - ".bold(), "Move Left".stylize()],
-                    vec![" D - ".bold(), "Move Right".stylize()],
-                    vec![
-                        " Space - ".bold(),
-                        "Toggle surrounding blocks (if able)".stylize(),
-                    ],
-                    vec![" Shift-H - ".bold(), "Show this help menu".stylize()],
-                    vec![" Q - ".bold(), "Quit".stylize()],
-                    vec![],
-                    vec!["Arrow keys can also be used to move around the ".stylize()],
-                    vec!["level".stylize()],
+                ];
+                content.append(&mut controls);
+                content.append(&mut vec![
                     vec![],
                     vec![
                         "X".green().on_green(),
@@ -523,326 +902,742 @@ impl Menu {
                     vec!["REPOSITORIES".bold().underlined()],
                     vec![],
                     vec!["Repositories allow you to play levels hosted ".stylize()],
-                    vec!["online. To add a repository, add a line in ".stylize()],
-                    vec!["your `$HOME/.l1t/repositories.l1t_conf` file:".stylize()],
+                    vec!["online. To add a repository, select ONLINE ".stylize()],
+                    vec!["and then `+ Add a repository`, or run:".stylize()],
                     vec![],
-                    vec!["My Repo Name = http://myrepourl.com".stylize()],
+                    vec!["l1t repo add \"My Repo\" http://myrepourl.com".stylize()],
                     vec![],
-                    vec!["The left side of the `=` is the repository ".stylize()],
-                    vec!["name and the right is the URL of the repo.".stylize()],
+                    vec![
+                        "Press ".stylize(),
+                        "Space".bold(),
+                        " on a repository to rename, ".stylize(),
+                    ],
+                    vec!["move or remove it.".stylize()],
                     vec![],
                     vec!["To host your own repo, checkout the repo ".stylize()],
                     vec!["documentation at:".stylize()],
                     vec![],
                     vec!["https://github.com/alex-laycalvert/l1t/".stylize()],
-                ]))
+                ]);
+                return Menu::open(MenuType::ScrollableMenu(content));
+            }
+            MenuType::Statistics(user_data) => {
+                let scores: Vec<(&String, &LevelScore)> = user_data.scores.iter().collect();
+                let total = |f: fn(&LevelScore) -> usize| -> usize {
+                    scores.iter().map(|(_, s)| f(s)).sum()
+                };
+                let time_played: u64 = scores.iter().map(|(_, s)| s.time_played).sum();
+                let mut completed: Vec<(String, String)> = vec![(
+                    "Core".to_string(),
+                    format!(
+                        "{}/{}",
+                        user_data.completed_core_levels.len(),
+                        Level::NUM_CORE_LEVELS
+                    ),
+                )];
+                let mut other_repos = user_data.completed_levels.len();
+                for repo in user_data.repositories.iter() {
+                    let prefix = repo.url.to_string() + "/";
+                    let count = user_data
+                        .completed_levels
+                        .iter()
+                        .filter(|l| l.url.starts_with(&prefix))
+                        .count();
+                    other_repos = other_repos.saturating_sub(count);
+                    completed.push((repo.name.to_string(), count.to_string()));
+                }
+                if other_repos > 0 {
+                    completed.push(("Other repositories".to_string(), other_repos.to_string()));
+                }
+                completed.push((
+                    "Files".to_string(),
+                    user_data.completed_files.len().to_string(),
+                ));
+                let totals: Vec<(String, String)> = vec![
+                    (
+                        "Play time".to_string(),
+                        format!(
+                            "{}:{:02}:{:02}",
+                            time_played / 3600,
+                            time_played / 60 % 60,
+                            time_played % 60
+                        ),
+                    ),
+                    ("Attempts".to_string(), total(|s| s.attempts).to_string()),
+                    ("Restarts".to_string(), total(|s| s.restarts).to_string()),
+                    ("Moves".to_string(), total(|s| s.moves).to_string()),
+                ];
+                let deaths: Vec<(String, String)> = vec![
+                    (
+                        "Zappers".to_string(),
+                        total(|s| s.zapper_deaths).to_string(),
+                    ),
+                    (
+                        "Laser beams".to_string(),
+                        total(|s| s.laser_deaths).to_string(),
+                    ),
+                ];
+                let mut recent: Vec<&CompletedRepoLevel> =
+                    user_data.completed_levels.iter().collect();
+                recent.sort_by_key(|l| std::cmp::Reverse(l.completed_at));
+                let recent: Vec<(String, String)> = recent
+                    .iter()
+                    .take(10)
+                    .map(|l| {
+                        (
+                            Menu::date_text(l.completed_at),
+                            format!("{} by {}", l.name, l.author),
+                        )
+                    })
+                    .collect();
+                let sections: [(&str, &Vec<(String, String)>); 4] = [
+                    ("TOTALS", &totals),
+                    ("LEVELS COMPLETED", &completed),
+                    ("DEATHS", &deaths),
+                    ("RECENT COMPLETIONS", &recent),
+                ];
+                let mut content: Vec<Vec<StyledContent<&str>>> = vec![];
+                for (title, rows) in sections.iter() {
+                    content.push(vec![]);
+                    content.push(vec![title.bold().underlined()]);
+                    content.push(vec![]);
+                    if rows.is_empty() {
+                        content.push(vec!["None yet".italic()]);
+                    }
+                    for (label, value) in rows.iter() {
+                        content.push(vec![
+                            label.as_str().bold(),
+                            " - ".stylize(),
+                            value.as_str().stylize(),
+                        ]);
+                    }
+                }
+                return Menu::open(MenuType::ScrollableMenu(content));
             }
             MenuType::ScrollableMenu(content) => {
                 let mut start_index: usize = 0;
                 let scroll_message = "  USE ARROW KEYS OR W, S TO SCROLL  ";
                 let fast_scroll_message = "  USE g AND G to GOTO TOP AND BOTTOM  ";
+                let accent = Settings::current().theme.accent();
                 loop {
-                    let (term_cols, term_rows) = size().unwrap_or((0, 0));
+                    let (term_cols, term_rows) = renderer::size();
                     let lines: usize = (term_rows - row_padding * 2) as usize - 6;
                     let start_row = (term_rows - lines as u16) / 2 - row_padding;
                     let end_row = (term_rows + lines as u16) / 2 + row_padding;
                     let start_col = (term_cols - 50) / 2 - col_padding;
                     let end_col = (term_cols + 50) / 2 + col_padding;
-                    execute!(
-                        stdout(),
-                        Clear(ClearType::All),
-                        MoveTo((term_cols - scroll_message.len() as u16) / 2, start_row - 1),
-                        Print(scroll_message.on_white().black().bold()),
-                        MoveTo(
-                            (term_cols - fast_scroll_message.len() as u16) / 2,
-                            end_row + 1
-                        ),
-                        Print(fast_scroll_message.on_white().black().bold())
-                    )
-                    .ok();
-                    Menu::draw_borders(start_row, end_row, start_col, end_col).ok();
-                    for (i, line) in content
-                        .iter()
-                        .enumerate()
-                        .take((start_index + lines).min(content.len()))
-                        .skip(start_index)
-                    {
-                        execute!(
-                            stdout(),
-                            MoveTo(
-                                start_col + col_padding + 1,
-                                start_row + row_padding + (i - start_index) as u16
-                            )
-                        )
-                        .ok();
-                        for piece in line.iter() {
-                            execute!(stdout(), Print(piece)).ok();
-                        }
-                    }
+                    let mut frame = renderer::blank();
+                    frame.print(
+                        start_row - 1,
+                        (term_cols - scroll_message.len() as u16) / 2,
+                        &scroll_message.on(accent).black().bold(),
+                    );
+                    frame.print(
+                        end_row + 1,
+                        (term_cols - fast_scroll_message.len() as u16) / 2,
+                        &fast_scroll_message.on(accent).black().bold(),
+                    );
+                    Menu::draw_borders(&mut frame, start_row, end_row, start_col, end_col);
+                    let end_index = (start_index + lines).min(content.len());
+                    Menu::print_lines(
+                        &mut frame,
+                        start_row + row_padding,
+                        start_col + col_padding + 1,
+                        &content[start_index.min(end_index)..end_index],
+                    );
+                    renderer::present(&frame).ok();
                     match Control::read_input() {
-                        Control::Up => {
+                        Control::Up | Control::ScrollUp => {
                             if start_index == 0 {
                                 continue;
                             }
                             start_index -= 1;
                         }
-                        Control::Down => {
+                        Control::Down | Control::ScrollDown => {
                             if start_index + lines >= content.len() {
                                 continue;
                             }
                             start_index += 1;
                         }
                         Control::GotoTop => start_index = 0,
-                        Control::GotoBottom => start_index = content.len() - lines,
+                        Control::GotoBottom => start_index = content.len().saturating_sub(lines),
                         Control::Select | Control::Quit => break,
                         _ => (),
                     }
                 }
             }
-            MenuType::CoreLevelSelection(completed_levels) => {
-                let num_levels = Level::NUM_CORE_LEVELS as f64;
-                let levels_per_row = num_levels.sqrt() as u16;
-                let num_rows = (num_levels / levels_per_row as f64).ceil() as u16;
+            MenuType::Selection(message, options) => {
+                let mut list = List::from_labels(message, options).centered();
+                let background = renderer::last();
+                loop {
+                    let (term_cols, term_rows) = renderer::size();
+                    let widest = list
+                        .items
+                        .iter()
+                        .map(|item| item.label.chars().count())
+                        .max()
+                        .unwrap_or(0)
+                        .max(message.chars().count() + 2);
+                    let cols = list.width(widest as u16).min(term_cols);
+                    let rows = list.height().min(term_rows.saturating_sub(2));
+                    let mut frame = background.clone();
+                    list.draw(
+                        &mut frame,
+                        (
+                            term_rows.saturating_sub(rows) / 2,
+                            term_cols.saturating_sub(cols) / 2,
+                        ),
+                        (rows, cols),
+                    );
+                    renderer::present(&frame).ok();
+                    match list.handle(Control::read_input()) {
+                        ListEvent::Selected(i) => return Some(Selection::Item(i)),
+                        ListEvent::Cancelled => return None,
+                        _ => (),
+                    }
+                }
+            }
+            MenuType::CoreLevelSelection(completed_levels, scores) => {
+                let levels_per_row = (Level::NUM_CORE_LEVELS as f64).sqrt() as usize;
                 let highest_available_level = match completed_levels.iter().max() {
-                    Some(n) => *n.min(&(Level::NUM_CORE_LEVELS - 1)) + 1,
+                    Some(n) => (*n + 1).min(Level::NUM_CORE_LEVELS - 1),
                     None => 0,
                 };
-                let highest_available_level = if highest_available_level >= Level::NUM_CORE_LEVELS {
-                    Level::NUM_CORE_LEVELS - 1
-                } else {
-                    highest_available_level
-                };
-                let mut current_selection = highest_available_level;
-                let message = "  SELECT A LEVEL  ";
+                let items = (0..Level::NUM_CORE_LEVELS)
+                    .map(|i| ListItem {
+                        label: format!("{:0>2}", i + 1),
+                        enabled: i <= highest_available_level,
+                    })
+                    .collect();
+                let mut list = List::new("SELECT A LEVEL", items)
+                    .grid(levels_per_row, 1, 2)
+                    .centered()
+                    .select(highest_available_level);
                 loop {
-                    let (term_cols, term_rows) = size().unwrap_or((0, 0));
-                    let start_row: u16 = (term_rows - num_rows * 2) / 2;
-                    let mut start_col: u16 = (term_cols / 2) - levels_per_row * 2;
-                    let end_row: u16 = (term_rows + num_rows * 2) / 2;
-                    let end_col: u16 = (term_cols / 2) + levels_per_row * 2;
-                    if (end_col - start_col + 1) % 2 != 0 {
-                        start_col -= 1;
-                    }
-                    execute!(
-                        stdout(),
-                        Clear(ClearType::All),
-                        MoveTo((term_cols - message.len() as u16) / 2, start_row - 1),
-                        Print(message.on_white().black().bold())
-                    )
-                    .ok();
-                    Menu::draw_borders(start_row, end_row, start_col, end_col).ok();
-                    for i in 0..Level::NUM_CORE_LEVELS {
-                        let is_available = i <= highest_available_level;
-                        let fg_color = if is_available && current_selection != i {
-                            Color::White
-                        } else {
-                            Color::Black
-                        };
-                        execute!(
-                            stdout(),
-                            MoveTo(
-                                (i as u16 % levels_per_row) * 2
-                                    + start_col
-                                    + col_padding
-                                    + (i as u16 % levels_per_row) * 2,
-                                start_row + 1 + (i as u16 / levels_per_row) * 2,
+                    let (term_cols, term_rows) = renderer::size();
+                    let cols = list.width(2).max(list.title.len() as u16 + 4);
+                    let rows = list.height();
+                    let start_row = term_rows.saturating_sub(rows) / 2;
+                    let start_col = (term_cols / 3).saturating_sub(cols / 2);
+                    let mut frame = renderer::blank();
+                    list.draw(&mut frame, (start_row, start_col), (rows, cols));
+                    if let Ok(mut level) = Level::core(list.selected) {
+                        level.update();
+                        let pane_col = start_col + cols + 5;
+                        Menu::draw_details(
+                            &mut frame,
+                            &level.info,
+                            Ok(&level),
+                            scores.get(&level.info.source.key()),
+                            (2, pane_col),
+                            (
+                                term_rows.saturating_sub(4),
+                                term_cols.saturating_sub(pane_col + 2),
                             ),
-                            SetForegroundColor(fg_color),
-                            SetBackgroundColor(if current_selection == i {
-                                Color::White
-                            } else {
-                                Color::Reset
-                            }),
-                            Print(format!("{:0>2}", (i + 1).to_string()).bold()),
-                        )
-                        .ok();
+                        );
                     }
-                    match Control::read_input() {
-                        Control::Up => {
-                            if current_selection == 0 {
-                                current_selection = highest_available_level;
-                            } else if current_selection >= levels_per_row.into() {
-                                current_selection -= levels_per_row as usize;
-                            } else {
-                                current_selection = 0;
-                            }
+                    renderer::present(&frame).ok();
+                    match list.handle(Control::read_input()) {
+                        ListEvent::Selected(i) => return Some(Selection::Item(i)),
+                        ListEvent::Cancelled => return None,
+                        _ => (),
+                    }
+                }
+            }
+            MenuType::RepositorySelection(repositories, selected) => {
+                let name_len = repositories
+                    .iter()
+                    .map(|repo| repo.name.chars().count())
+                    .max()
+                    .unwrap_or(0);
+                let mut labels: Vec<String> = repositories
+                    .iter()
+                    .map(|repo| format!("{: <name_len$}  {}", repo.name, repo.url))
+                    .collect();
+                labels.push("+ Add a repository".to_string());
+                let mut list = List::from_labels("SELECT A REPO", labels).select(selected);
+                list.status = format!(
+                    "{} to rename, move or remove a repository",
+                    Menu::key_name(Control::Action)
+                );
+                loop {
+                    let (term_cols, term_rows) = renderer::size();
+                    let cols = term_cols.saturating_sub(4).min(200);
+                    let rows = list.height().min(term_rows.saturating_sub(4));
+                    let mut frame = renderer::blank();
+                    list.draw(
+                        &mut frame,
+                        (
+                            term_rows.saturating_sub(rows) / 2,
+                            term_cols.saturating_sub(cols) / 2,
+                        ),
+                        (rows, cols),
+                    );
+                    renderer::present(&frame).ok();
+                    match list.handle(Control::read_input()) {
+                        ListEvent::Selected(i) if i == repositories.len() => {
+                            return Some(Selection::Add)
                         }
-                        Control::Down => {
-                            if current_selection == highest_available_level {
-                                current_selection = 0;
-                            } else if current_selection
-                                < (Level::NUM_CORE_LEVELS as i16 - levels_per_row as i16) as usize
-                                && current_selection + (levels_per_row as usize)
-                                    <= highest_available_level
-                            {
-                                current_selection += levels_per_row as usize;
-                            } else {
-                                current_selection = highest_available_level;
-                            }
+                        ListEvent::Selected(i) => return Some(Selection::Item(i)),
+                        ListEvent::Ignored(Control::Action)
+                            if list.selected < repositories.len() =>
+                        {
+                            return Some(Selection::Edit(list.selected))
                         }
-                        Control::Left => {
-                            if current_selection == 0 {
-                                current_selection = highest_available_level;
-                            } else {
-                                current_selection -= 1;
-                            }
+                        ListEvent::Cancelled => return None,
+                        _ => (),
+                    }
+                }
+            }
+            MenuType::TextPrompt(message, initial) => {
+                let mut text = initial.to_string();
+                let background = renderer::last();
+                loop {
+                    let (term_cols, term_rows) = renderer::size();
+                    let width = (message.chars().count() as u16 + 6).max(50).min(term_cols);
+                    let start_row = term_rows.saturating_sub(5) / 2;
+                    let start_col = term_cols.saturating_sub(width) / 2;
+                    let mut frame = background.clone();
+                    Menu::draw_borders(
+                        &mut frame,
+                        start_row,
+                        start_row + 4,
+                        start_col,
+                        start_col + width - 1,
+                    );
+                    frame.print(start_row + 1, start_col + 2, &message.stylize());
+                    let chars: Vec<char> = text.chars().collect();
+                    let shown = chars.len().saturating_sub(width.saturating_sub(5) as usize);
+                    let visible: String = chars[shown..].iter().collect();
+                    frame.print(start_row + 3, start_col + 2, &format!("{visible}_").bold());
+                    renderer::present(&frame).ok();
+                    match TextInput::read() {
+                        TextInput::Char(c) => text.push(c),
+                        TextInput::Backspace => {
+                            text.pop();
+                        }
+                        TextInput::Done => return Some(Selection::Text(text)),
+                        TextInput::Cancel => return None,
+                        TextInput::None => (),
+                    }
+                }
+            }
+            MenuType::RepositoryLevelSelection(repository, completed_levels, scores) => {
+                let levels = &repository.levels;
+                let is_completed = |level: &LevelInfo| match &level.source {
+                    LevelSource::Url(url) => completed_levels.iter().any(|l| {
+                        l.url == *url || (l.name == level.name && l.author == level.author)
+                    }),
+                    _ => false,
+                };
+                let mut tags: Vec<&String> =
+                    levels.iter().flat_map(|l| l.metadata.tags.iter()).collect();
+                tags.sort();
+                tags.dedup();
+                let mut filters = vec![
+                    LevelFilter::All,
+                    LevelFilter::Uncompleted,
+                    LevelFilter::Completed,
+                ];
+                filters.extend(tags.into_iter().map(|t| LevelFilter::Tag(t.to_string())));
+                let mut filter = 0;
+                let mut sort = LevelSort::Listing;
+                let mut query = String::new();
+                let mut searching = false;
+                let mut list = List::from_labels("SELECT A LEVEL", vec![]);
+                list.empty = "No levels match".to_string();
+                let mut previews: HashMap<usize, Result<Level, String>> = HashMap::new();
+                loop {
+                    let lowercase_query = query.to_lowercase();
+                    let mut visible: Vec<usize> = (0..levels.len())
+                        .filter(|&i| {
+                            let level = &levels[i];
+                            let matches_query = [&level.name, &level.author, &level.description]
+                                .iter()
+                                .any(|s| s.to_lowercase().contains(&lowercase_query));
+                            matches_query
+                                && match &filters[filter] {
+                                    LevelFilter::All => true,
+                                    LevelFilter::Uncompleted => !is_completed(level),
+                                    LevelFilter::Completed => is_completed(level),
+                                    LevelFilter::Tag(tag) => level.metadata.tags.contains(tag),
+                                }
+                        })
+                        .collect();
+                    match sort {
+                        LevelSort::Listing => (),
+                        LevelSort::Name => visible.sort_by_key(|&i| levels[i].name.to_lowercase()),
+                        LevelSort::Author => {
+                            visible.sort_by_key(|&i| levels[i].author.to_lowercase())
                         }
-                        Control::Right => {
-                            if current_selection >= highest_available_level {
-                                current_selection = 0;
+                        LevelSort::Difficulty => visible.sort_by_key(|&i| {
+                            let difficulty = levels[i].metadata.difficulty;
+                            (difficulty.is_none(), difficulty)
+                        }),
+                        LevelSort::Date => visible.sort_by_key(|&i| {
+                            let date = &levels[i].metadata.date;
+                            (date.is_none(), std::cmp::Reverse(date.clone()))
+                        }),
+                    }
+                    let (term_cols, term_rows) = renderer::size();
+                    let cols = term_cols.saturating_sub(4).min(200);
+                    let item_width = cols.saturating_sub(4) as usize;
+                    let level_name_len = (item_width / 5).saturating_sub(2);
+                    let level_author_len = level_name_len;
+                    list.items = visible
+                        .iter()
+                        .map(|&i| {
+                            let level = &levels[i];
+                            let score = match &level.source {
+                                LevelSource::Url(url) => scores.get(url).cloned().unwrap_or_default(),
+                                _ => LevelScore::default(),
+                            };
+                            let rating = if score.stars > 0 {
+                                star_rating(score.stars)
                             } else {
-                                current_selection += 1;
+                                "   ".to_string()
+                            };
+                            let best_moves = match score.best_moves {
+                                Some(m) => m.to_string(),
+                                None => "-".to_string(),
+                            };
+                            ListItem::new(format!(
+                                "{} {} {: >5} {: >5} {: <level_name_len$} {: <level_author_len$} {}",
+                                if is_completed(level) { "\u{2713}" } else { " " },
+                                rating,
+                                best_moves,
+                                Menu::time_text(score.best_time),
+                                level.name.chars().take(level_name_len).collect::<String>(),
+                                level.author.chars().take(level_author_len).collect::<String>(),
+                                level.description
+                            ))
+                        })
+                        .collect();
+                    list.selected = list.selected.min(visible.len().saturating_sub(1));
+                    let search = if searching {
+                        format!("Search: {query}_")
+                    } else if query.is_empty() {
+                        format!("Search ({})", Menu::key_name(Control::Search))
+                    } else {
+                        format!("Search ({}): {query}", Menu::key_name(Control::Search))
+                    };
+                    list.status = format!(
+                        "{search}   Filter ({}): {}   Sort ({}): {}   {}",
+                        Menu::key_name(Control::Filter),
+                        filters[filter].name(),
+                        Menu::key_name(Control::Sort),
+                        sort.name(),
+                        Menu::cache_text(repository.cache_status)
+                    );
+                    let start_row: u16 = 2;
+                    let start_col = (term_cols - cols) / 2;
+                    let rows = list.height().min(term_rows / 2);
+                    let mut frame = renderer::blank();
+                    list.draw(&mut frame, (start_row, start_col), (rows, cols));
+                    let pane_row = start_row + rows + 2;
+                    let pane_rows = term_rows.saturating_sub(pane_row + 1);
+                    if pane_rows > 0 && !visible.is_empty() && !searching {
+                        let i = visible[list.selected];
+                        let preview = previews.entry(i).or_insert_with(|| {
+                            let mut loading = frame.clone();
+                            loading.print(pane_row, start_col + 1, &"Loading preview...".italic());
+                            renderer::present(&loading).ok();
+                            Menu::fetch_preview(&levels[i])
+                        });
+                        let info = &levels[i];
+                        Menu::draw_details(
+                            &mut frame,
+                            info,
+                            preview.as_ref().map_err(|e| e.as_str()),
+                            scores.get(&info.source.key()),
+                            (pane_row, start_col + 1),
+                            (pane_rows, cols - 2),
+                        );
+                    }
+                    renderer::present(&frame).ok();
+                    if searching {
+                        match TextInput::read() {
+                            TextInput::Char(c) => query.push(c),
+                            TextInput::Backspace => {
+                                query.pop();
+                            }
+                            TextInput::Done => searching = false,
+                            TextInput::Cancel => {
+                                query.clear();
+                                searching = false;
                             }
+                            TextInput::None => (),
+                        }
+                        continue;
+                    }
+                    match list.handle(Control::read_input()) {
+                        ListEvent::Selected(i) => return Some(Selection::Item(visible[i])),
+                        ListEvent::Ignored(Control::Search) => searching = true,
+                        ListEvent::Ignored(Control::Filter) => {
+                            filter = (filter + 1) % filters.len()
                         }
-                        Control::Quit => return None,
-                        Control::Select => return Some(Selection::Item(current_selection)),
+                        ListEvent::Ignored(Control::Sort) => sort = sort.next(),
+                        ListEvent::Cancelled => return Some(Selection::Quit),
                         _ => (),
                     }
                 }
             }
-            MenuType::RepositorySelection(repositories) => {
-                let message = " SELECT A REPO ";
-                let mut current_selection = 0;
-                loop {
-                    let (term_cols, term_rows) = size().unwrap_or((0, 0));
-                    let num_cols = (term_cols - 4).min(200) as usize;
-                    let repo_name_len = num_cols / 2 - 2;
-                    let repo_url_len = num_cols - 4 - repo_name_len;
-                    let start_row: u16 = (term_rows - repositories.len() as u16) / 2;
-                    let start_col: u16 = (term_cols - num_cols as u16) / 2;
-                    let end_row: u16 = (term_rows + repositories.len() as u16) / 2 + 1;
-                    let end_col: u16 = (term_cols + num_cols as u16) / 2;
-                    execute!(
-                        stdout(),
-                        Clear(ClearType::All),
-                        MoveTo((term_cols - message.len() as u16) / 2, start_row - 1),
-                        Print(message.on_white().black().bold())
-                    )
-                    .ok();
-                    Menu::draw_borders(start_row, end_row, start_col, end_col).ok();
-                    for (i, repo) in repositories.iter().enumerate() {
-                        execute!(
-                            stdout(),
-                            SetBackgroundColor(if i == current_selection {
-                                Color::White
-                            } else {
-                                Color::Reset
-                            }),
-                            SetForegroundColor(if i == current_selection {
-                                Color::Black
-                            } else {
-                                Color::White
-                            }),
-                            MoveTo(start_col + 1, start_row + i as u16 + 1),
-                            Print(
-                                format!(
-                                    " {: <repo_name_len$} {: <repo_url_len$} ",
-                                    &repo.name[0..repo.name.len().min(repo_name_len)],
-                                    &repo.url[0..repo.url.len().min(repo_url_len)],
-                                )
-                                .bold()
+            MenuType::LocalLevelSelection(dir, entries, scores) => {
+                let items = entries
+                    .iter()
+                    .map(|entry| match entry {
+                        LocalEntry::Parent(_) => ListItem::new("  ../".to_string()),
+                        LocalEntry::Directory(path) => ListItem::new(format!(
+                            "  {}/",
+                            path.file_name().unwrap_or_default().to_string_lossy()
+                        )),
+                        LocalEntry::Level(level, completed) => ListItem::new(format!(
+                            "{} {}",
+                            if *completed { "\u{2713}" } else { " " },
+                            level.info.name
+                        )),
+                        LocalEntry::Broken(path, error) => ListItem {
+                            label: format!(
+                                "! {}: {error}",
+                                path.file_name().unwrap_or_default().to_string_lossy()
                             ),
-                            ResetColor
-                        )
-                        .ok();
-                    }
-                    match Control::read_input() {
-                        Control::Up => {
-                            if current_selection == 0 {
-                                current_selection = repositories.len() - 1;
-                            } else {
-                                current_selection -= 1;
-                            }
+                            enabled: false,
+                        },
+                    })
+                    .collect();
+                let mut list = List::new("LOCAL LEVELS", items).select(0);
+                list.status = dir.to_string_lossy().to_string();
+                list.empty = "No levels here".to_string();
+                loop {
+                    let (term_cols, term_rows) = renderer::size();
+                    let num_cols = term_cols.saturating_sub(4).min(200);
+                    let start_col = (term_cols - num_cols) / 2;
+                    let list_cols = num_cols / 3;
+                    let rows = term_rows.saturating_sub(3);
+                    let mut frame = renderer::blank();
+                    list.draw(&mut frame, (1, start_col), (rows, list_cols));
+                    let pane_col = start_col + list_cols + 2;
+                    let pane_cols = (start_col + num_cols).saturating_sub(pane_col + 1);
+                    let mut errors: Vec<String> = vec![];
+                    for entry in entries.iter() {
+                        if let LocalEntry::Broken(path, error) = entry {
+                            let name = path.file_name().unwrap_or_default().to_string_lossy();
+                            errors.extend(Menu::wrap(
+                                &format!("{name}: {error}"),
+                                pane_cols as usize,
+                            ));
                         }
-                        Control::Down => {
-                            if current_selection == repositories.len() - 1 {
-                                current_selection = 0;
-                            } else {
-                                current_selection += 1;
-                            }
+                    }
+                    let errors_rows = if errors.is_empty() {
+                        0
+                    } else {
+                        errors.len() as u16 + 2
+                    };
+                    let errors_row = (rows + 1).saturating_sub(errors_rows);
+                    if !errors.is_empty() {
+                        frame.print(errors_row, pane_col, &"Couldn't be loaded:".bold());
+                        for (i, line) in errors.iter().enumerate() {
+                            let line = line.as_str().with(Color::DarkGrey);
+                            frame.print(errors_row + 1 + i as u16, pane_col, &line);
                         }
-                        Control::Select => return Some(Selection::Item(current_selection)),
-                        Control::None => continue,
-                        _ => break,
+                    }
+                    if let Some(LocalEntry::Level(level, _)) = entries.get(list.selected) {
+                        Menu::draw_details(
+                            &mut frame,
+                            &level.info,
+                            Ok(level),
+                            scores.get(&level.info.source.score_key(&level.hash())),
+                            (2, pane_col),
+                            (errors_row.saturating_sub(3), pane_cols),
+                        );
+                    }
+                    renderer::present(&frame).ok();
+                    match list.handle(Control::read_input()) {
+                        ListEvent::Selected(i) => return Some(Selection::Item(i)),
+                        ListEvent::Cancelled => return Some(Selection::Quit),
+                        _ => (),
                     }
                 }
             }
-            MenuType::RepositoryLevelSelection(repository, completed_levels) => {
-                let message = " SELECT A REPO ";
-                let mut current_selection = 0;
+            MenuType::PauseMenu => {
+                let options = [
+                    (Selection::Resume, "Resume"),
+                    (Selection::Restart, "Restart"),
+                    (Selection::Undo, "Undo"),
+                    (Selection::Hint, "Hint"),
+                    (Selection::Help, "Help"),
+                    (Selection::Settings, "Settings"),
+                    (Selection::SaveAndQuit, "Save & Quit"),
+                    (Selection::Quit, "Quit"),
+                ];
+                let labels = options.iter().map(|(_, label)| label.to_string()).collect();
+                let mut list = List::from_labels("PAUSED", labels).centered();
+                let width: u16 = 24;
+                let background = renderer::last();
                 loop {
-                    let (term_cols, term_rows) = size().unwrap_or((0, 0));
-                    let num_cols = (term_cols - 4).min(200) as usize;
-                    let level_name_len = num_cols / 5 - 2;
-                    let level_author_len = level_name_len;
-                    let level_desc_len = num_cols - level_name_len - level_author_len - 6;
-                    let start_row: u16 = (term_rows - repository.levels.len() as u16) / 2;
-                    let start_col: u16 = (term_cols - num_cols as u16) / 2;
-                    let end_row: u16 = (term_rows + repository.levels.len() as u16) / 2 + 1;
-                    let end_col: u16 = (term_cols + num_cols as u16) / 2;
-                    execute!(
-                        stdout(),
-                        Clear(ClearType::All),
-                        MoveTo((term_cols - message.len() as u16) / 2, start_row - 1),
-                        Print(message.on_white().black().bold())
-                    )
-                    .ok();
-                    Menu::draw_borders(start_row, end_row, start_col, end_col).ok();
-                    for (i, level) in repository.levels.iter().enumerate() {
-                        if let LevelSource::Url(url) = &level.source {
-                            let completed = completed_levels.iter().any(|l| {
-                                l.url == *url || (l.name == level.name && l.author == level.author)
-                            });
-                            execute!(
-                                stdout(),
-                                SetBackgroundColor(if i == current_selection {
-                                    Color::White
-                                } else {
-                                    Color::Reset
-                                }),
-                                SetForegroundColor(if i == current_selection {
-                                    Color::Black
-                                } else {
-                                    Color::White
-                                }),
-                                MoveTo(start_col + 1, start_row + i as u16 + 1),
-                                Print(
-                                    format!(
-                                        " {} {: <level_name_len$} {: <level_author_len$} {: <level_desc_len$}",
-                                        if completed { "\u{2713}" } else { " " },
-                                        &level.name[0..level.name.len().min(level_name_len)],
-                                        &level.author[0..level.author.len().min(level_author_len)],
-                                        &level.description[0..level.description.len().min(level_desc_len)])
-                                    .bold()
-                                ),
-                                ResetColor
-                            )
-                            .ok();
+                    let (term_cols, term_rows) = renderer::size();
+                    let rows = list.height();
+                    let mut frame = background.clone();
+                    list.draw(
+                        &mut frame,
+                        (
+                            term_rows.saturating_sub(rows) / 2,
+                            term_cols.saturating_sub(width) / 2,
+                        ),
+                        (rows, width),
+                    );
+                    renderer::present(&frame).ok();
+                    match list.handle(Control::read_input()) {
+                        ListEvent::Selected(i) => return Some(options[i].0.clone()),
+                        ListEvent::Cancelled => return Some(Selection::Resume),
+                        _ => (),
+                    }
+                }
+            }
+            MenuType::SettingsMenu => {
+                let width: u16 = 44;
+                let background = renderer::last();
+                let mut list = List::from_labels("SETTINGS", vec![]);
+                list.status = "Enter, Left and Right change a setting".to_string();
+                let on_off = |on: bool| if on { "On" } else { "Off" }.to_string();
+                loop {
+                    let settings = Settings::current();
+                    let options = [
+                        ("Theme", settings.theme.name().to_string()),
+                        ("Animations", on_off(settings.animations)),
+                        ("Animation step", format!("{} ms", settings.animation_speed)),
+                        ("Win message delay", format!("{} ms", settings.win_delay)),
+                        ("Colorblind mode", on_off(settings.colorblind)),
+                        ("Double width cells", on_off(settings.double_width)),
+                        ("Key bindings", "...".to_string()),
+                    ];
+                    list.items = options
+                        .iter()
+                        .map(|(label, value)| {
+                            ListItem::new(format!(
+                                "{:<w$}{:>10}",
+                                label,
+                                value,
+                                w = (width - 14) as usize
+                            ))
+                        })
+                        .collect();
+                    let (term_cols, term_rows) = renderer::size();
+                    let rows = list.height();
+                    let mut frame = background.clone();
+                    list.draw(
+                        &mut frame,
+                        (
+                            term_rows.saturating_sub(rows) / 2,
+                            term_cols.saturating_sub(width) / 2,
+                        ),
+                        (rows, width),
+                    );
+                    renderer::present(&frame).ok();
+                    let (i, step) = match list.handle(Control::read_input()) {
+                        ListEvent::Selected(i) => (i, 1),
+                        ListEvent::Ignored(Control::Left) => (list.selected, -1),
+                        ListEvent::Ignored(Control::Right) => (list.selected, 1),
+                        ListEvent::Cancelled => break,
+                        _ => continue,
+                    };
+                    let saved = match i {
+                        0 => {
+                            let theme = settings.theme.cycle(step);
+                            Settings::update(|s| s.theme = theme)
+                        }
+                        1 => {
+                            let animations = !settings.animations;
+                            Settings::update(|s| s.animations = animations)
+                        }
+                        2 => {
+                            let speed =
+                                (settings.animation_speed as isize + step * 5).clamp(5, 100) as u64;
+                            Settings::update(|s| s.animation_speed = speed)
+                        }
+                        3 => {
+                            let delay =
+                                (settings.win_delay as isize + step * 100).clamp(0, 3000) as u64;
+                            Settings::update(|s| s.win_delay = delay)
+                        }
+                        4 => {
+                            let colorblind = !settings.colorblind;
+                            Settings::update(|s| s.colorblind = colorblind)
+                        }
+                        5 => {
+                            let double_width = !settings.double_width;
+                            Settings::update(|s| s.double_width = double_width)
+                        }
+                        _ => {
+                            Menu::open(MenuType::KeyBindings);
+                            continue;
                         }
+                    };
+                    if let Err(e) = saved {
+                        Menu::open(MenuType::Message(&e));
                     }
-                    match Control::read_input() {
-                        Control::Up => {
-                            if current_selection == 0 {
-                                current_selection = repository.levels.len() - 1;
-                            } else {
-                                current_selection -= 1;
+                }
+            }
+            MenuType::KeyBindings => {
+                let width: u16 = 62;
+                let background = renderer::last();
+                let mut list = List::from_labels("KEY BINDINGS", vec![]);
+                loop {
+                    let keymap = Keymap::current();
+                    list.items = Control::BINDABLE
+                        .iter()
+                        .map(|control| {
+                            let keys: Vec<String> = keymap
+                                .keys(*control)
+                                .iter()
+                                .map(|k| k.to_string())
+                                .collect();
+                            ListItem::new(format!(
+                                "{:<36}{:>w$}",
+                                control.description(),
+                                keys.join(", "),
+                                w = (width - 40) as usize
+                            ))
+                        })
+                        .collect();
+                    list.status = "Enter replaces the keys of a control".to_string();
+                    let (term_cols, term_rows) = renderer::size();
+                    let rows = list.height().min(term_rows.saturating_sub(2));
+                    let position = (
+                        term_rows.saturating_sub(rows) / 2,
+                        term_cols.saturating_sub(width) / 2,
+                    );
+                    let mut frame = background.clone();
+                    list.draw(&mut frame, position, (rows, width));
+                    renderer::present(&frame).ok();
+                    let control = match list.handle(Control::read_input()) {
+                        ListEvent::Selected(i) => Control::BINDABLE[i],
+                        ListEvent::Cancelled => break,
+                        _ => continue,
+                    };
+                    list.status =
+                        format!("Press a key for `{}`, Esc to cancel", control.description());
+                    let mut frame = background.clone();
+                    list.draw(&mut frame, position, (rows, width));
+                    renderer::present(&frame).ok();
+                    let key = match Key::read() {
+                        Some(k) => k,
+                        None => continue,
+                    };
+                    let keys = BTreeMap::from([(control.name().to_string(), key.to_string())]);
+                    match keymap.with_keys(&keys) {
+                        Ok(keymap) => {
+                            Keymap::set(keymap);
+                            if let Err(e) = Settings::update(|s| s.keys.extend(keys.clone())) {
+                                Menu::open(MenuType::Message(&e));
                             }
                         }
-                        Control::Down => {
-                            if current_selection == repository.levels.len() - 1 {
-                                current_selection = 0;
-                            } else {
-                                current_selection += 1;
-                            }
+                        Err(e) => {
+                            Menu::open(MenuType::Message(&e));
                         }
-                        Control::Select => return Some(Selection::Item(current_selection)),
-                        Control::Quit => return Some(Selection::Quit),
-                        Control::None => continue,
-                        _ => break,
                     }
                 }
             }
-            _ => (),
         }
         None
     }
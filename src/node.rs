@@ -1,52 +1,47 @@
-use crate::direction::Direction;
-use crossterm::{
-    cursor::MoveTo,
-    execute,
-    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor, Stylize},
-};
-use std::io::stdout;
+use crate::{direction::Direction, render::Cell, settings::Settings};
+use crossterm::style::Color;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Player {
     pub dead: bool,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Block;
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Wall;
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Switch {
     pub on: bool,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ToggleBlock {
     pub visible: bool,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Button {
     pub pressed: bool,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Mirror {
     pub dir: Direction,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Laser {
     pub on: bool,
     pub dir: Direction,
     pub shooting_at: Vec<(u16, u16, char, char)>,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Statue {
     pub lit: bool,
     pub reversed: bool,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Zapper {
     pub lit: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum NodeType {
     Player(Player),
     Block(Block),
@@ -60,7 +55,7 @@ pub enum NodeType {
     Zapper(Zapper),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Node {
     pub node_type: NodeType,
     pub row: u16,
@@ -259,134 +254,108 @@ impl Node {
         }
     }
 
-    pub fn draw_overlay(&self, offset: (u16, u16)) -> crossterm::Result<()> {
-        let mut stdout = stdout();
+    /// Returns the cells covered by this node's laser beam, if it is a
+    /// `Laser` that is currently shooting. The last cell of the beam is
+    /// drawn as an arrow pointing in the direction of travel. Where the
+    /// beam turns, the cell is drawn as horizontal only if the beam enters
+    /// or leaves it from the right so double width cells join up.
+    pub fn beam_cells(&self) -> Vec<(u16, u16, Cell)> {
+        let mut cells = vec![];
         if let NodeType::Laser(l) = &self.node_type {
             if l.shooting_at.is_empty() {
-                return Ok(());
+                return cells;
             }
             for i in 0..(l.shooting_at.len() - 1) {
                 let pos = l.shooting_at[i];
-                execute!(
-                    stdout,
-                    SetForegroundColor(Color::Rgb { r: 255, g: 0, b: 0 }),
-                    MoveTo(pos.1 + offset.1, pos.0 + offset.0),
-                )?;
-                if i == l.shooting_at.len() - 2 {
-                    execute!(stdout, Print(pos.3.bold()),)?;
+                let next = l.shooting_at[i + 1];
+                let turns = if pos.2 == '-' {
+                    next.0 != pos.0
                 } else {
-                    execute!(stdout, Print(pos.2.bold()),)?;
-                }
+                    next.1 != pos.1
+                };
+                let glyph = if i == l.shooting_at.len() - 2 {
+                    pos.3
+                } else if turns {
+                    if pos.3 == '<' || next.1 > pos.1 {
+                        '-'
+                    } else {
+                        '|'
+                    }
+                } else {
+                    pos.2
+                };
+                cells.push((pos.0, pos.1, Cell::new(glyph, RED, Color::Reset)));
             }
         }
-        execute!(stdout, ResetColor)
+        cells
     }
 
-    pub fn draw(&self, offset: (u16, u16)) -> crossterm::Result<()> {
-        let mut stdout = stdout();
-        match &self.node_type {
-            NodeType::Player(p) => execute!(
-                stdout,
-                SetForegroundColor(if p.dead { RED } else { Color::Green }),
-                SetBackgroundColor(Color::Green),
-                MoveTo(self.col + offset.1, self.row + offset.0),
-                Print("X".bold()),
-            ),
-            NodeType::Block(_) => execute!(
-                stdout,
-                SetForegroundColor(Color::Grey),
-                SetBackgroundColor(Color::Grey),
-                MoveTo(self.col + offset.1, self.row + offset.0),
-                Print("B".bold()),
-            ),
-            NodeType::Wall(_) => execute!(
-                stdout,
-                SetForegroundColor(Color::White),
-                SetBackgroundColor(Color::White),
-                MoveTo(self.col + offset.1, self.row + offset.0),
-                Print("I".bold()),
-            ),
-            NodeType::Switch(s) => execute!(
-                stdout,
-                SetForegroundColor(Color::Black),
-                SetBackgroundColor(if s.on { Color::Yellow } else { Color::Red }),
-                MoveTo(self.col + offset.1, self.row + offset.0),
-                Print("s".bold()),
-            ),
+    /// Returns the glyph and colors used to draw this node, or `None` if
+    /// the node is currently invisible.
+    pub fn cell(&self) -> Option<Cell> {
+        // Colorblind mode swaps red and green for colors that can still be
+        // told apart with red-green color blindness.
+        let (off, player) = if Settings::current().colorblind {
+            (Color::Blue, Color::Cyan)
+        } else {
+            (Color::Red, Color::Green)
+        };
+        let (glyph, fg, bg) = match &self.node_type {
+            NodeType::Player(p) => ('X', if p.dead { RED } else { player }, player),
+            NodeType::Block(_) => ('B', Color::Grey, Color::Grey),
+            NodeType::Wall(_) => ('I', Color::White, Color::White),
+            NodeType::Switch(s) => ('s', Color::Black, if s.on { Color::Yellow } else { off }),
             NodeType::ToggleBlock(t) => {
-                if t.visible {
-                    execute!(
-                        stdout,
-                        SetForegroundColor(Color::Magenta),
-                        SetBackgroundColor(Color::Magenta),
-                        MoveTo(self.col + offset.1, self.row + offset.0),
-                        Print("T".bold())
-                    )
-                } else {
-                    Ok(())
+                if !t.visible {
+                    return None;
                 }
+                ('T', Color::Magenta, Color::Magenta)
             }
-            NodeType::Button(b) => execute!(
-                stdout,
-                SetForegroundColor(Color::Black),
-                SetBackgroundColor(if b.pressed { Color::Yellow } else { Color::Red }),
-                MoveTo(self.col + offset.1, self.row + offset.0),
-                Print("b".bold()),
+            NodeType::Button(b) => (
+                'b',
+                Color::Black,
+                if b.pressed { Color::Yellow } else { off },
             ),
-            NodeType::Mirror(m) => execute!(
-                stdout,
-                SetForegroundColor(if self.moveable {
+            NodeType::Mirror(m) => (
+                if matches!(m.dir, Direction::FORWARD) {
+                    '/'
+                } else {
+                    '\\'
+                },
+                if self.moveable {
                     Color::Black
                 } else {
                     Color::White
-                }),
-                SetBackgroundColor(if self.moveable {
+                },
+                if self.moveable {
                     Color::White
                 } else {
                     Color::Reset
-                }),
-                MoveTo(self.col + offset.1, self.row + offset.0),
-                Print(if matches!(m.dir, Direction::FORWARD) {
-                    "/".bold()
-                } else {
-                    "\\".bold()
-                }),
+                },
             ),
-            NodeType::Laser(l) => execute!(
-                stdout,
-                SetForegroundColor(if l.on { RED } else { DIM_RED }),
-                SetBackgroundColor(if l.on { RED } else { DIM_RED }),
-                MoveTo(self.col + offset.1, self.row + offset.0),
-                Print("L".bold()),
+            NodeType::Laser(l) => (
+                'L',
+                if l.on { RED } else { DIM_RED },
+                if l.on { RED } else { DIM_RED },
             ),
             NodeType::Statue(s) => {
                 if s.reversed {
-                    execute!(
-                        stdout,
-                        SetForegroundColor(Color::Black),
-                        SetBackgroundColor(if s.lit { DIM_YELLOW } else { YELLOW }),
-                        MoveTo(self.col + offset.1, self.row + offset.0),
-                        Print("R".bold()),
-                    )
+                    ('R', Color::Black, if s.lit { DIM_YELLOW } else { YELLOW })
                 } else {
-                    execute!(
-                        stdout,
-                        SetForegroundColor(if s.lit { YELLOW } else { DIM_YELLOW }),
-                        SetBackgroundColor(if s.lit { YELLOW } else { DIM_YELLOW }),
-                        MoveTo(self.col + offset.1, self.row + offset.0),
-                        Print("S".bold()),
+                    (
+                        'S',
+                        if s.lit { YELLOW } else { DIM_YELLOW },
+                        if s.lit { YELLOW } else { DIM_YELLOW },
                     )
                 }
             }
-            NodeType::Zapper(z) => execute!(
-                stdout,
-                SetForegroundColor(if z.lit { Color::Black } else { Color::Yellow }),
-                SetBackgroundColor(if z.lit { Color::Yellow } else { Color::Black }),
-                MoveTo(self.col + offset.1, self.row + offset.0),
-                Print("Z".bold()),
+            NodeType::Zapper(z) => (
+                'Z',
+                if z.lit { Color::Black } else { Color::Yellow },
+                if z.lit { Color::Yellow } else { Color::Black },
             ),
-        }?;
-        execute!(stdout, ResetColor)
+        };
+        Some(Cell::new(glyph, fg, bg))
     }
 
     pub fn would_move_to(&mut self, dir: Direction) -> (u16, u16) {
@@ -485,4 +454,35 @@ impl Node {
             l.shooting_at = shooting_at
         }
     }
+
+    /// Returns the single toggleable piece of state this node holds
+    /// (lit, on, pressed, visible, mirror direction, ...) as a `bool`.
+    pub fn state(&self) -> bool {
+        match &self.node_type {
+            NodeType::Player(p) => p.dead,
+            NodeType::Laser(l) => l.on,
+            NodeType::Statue(s) => s.lit,
+            NodeType::Zapper(z) => z.lit,
+            NodeType::Mirror(m) => matches!(m.dir, Direction::FORWARD),
+            NodeType::Button(b) => b.pressed,
+            NodeType::Switch(s) => s.on,
+            NodeType::ToggleBlock(t) => t.visible,
+            _ => false,
+        }
+    }
+
+    /// Restores the state previously returned by `Node::state`.
+    pub fn set_state(&mut self, state: bool) {
+        if let NodeType::Mirror(m) = &mut self.node_type {
+            m.dir = if state {
+                Direction::FORWARD
+            } else {
+                Direction::BACKWARD
+            };
+        } else if state {
+            self.turn_on();
+        } else {
+            self.turn_off();
+        }
+    }
 }
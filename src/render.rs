@@ -0,0 +1,333 @@
+use crate::{level::Level, solver};
+use clap::ValueEnum;
+use crossterm::style::{Attribute, Attributes, Color, ContentStyle, StyledContent};
+use std::fmt::Display;
+
+/// Width, in pixels, of a single grid cell when rendering to SVG.
+const SVG_CELL_WIDTH: u16 = 10;
+
+/// Height, in pixels, of a single grid cell when rendering to SVG.
+const SVG_CELL_HEIGHT: u16 = 20;
+
+/// Color used for cells whose background is `Color::Reset`.
+const BACKGROUND: &str = "#000000";
+
+/// Color used for glyphs whose foreground is `Color::Reset`.
+const FOREGROUND: &str = "#ffffff";
+
+/// The output format of a static level render.
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum RenderFormat {
+    Svg,
+    Html,
+    Ansi,
+}
+
+/// The state of the level to render.
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum RenderState {
+    /// The level as it is loaded from its source.
+    Initial,
+    /// The level after playing the shortest solution found by the solver.
+    Solved,
+}
+
+/// A single character on screen with its colors and attributes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub glyph: char,
+    pub fg: Color,
+    pub bg: Color,
+    pub attributes: Attributes,
+}
+
+impl Cell {
+    /// Creates a bold cell, which is how every level node is drawn.
+    pub fn new(glyph: char, fg: Color, bg: Color) -> Cell {
+        Cell {
+            glyph,
+            fg,
+            bg,
+            attributes: Attribute::Bold.into(),
+        }
+    }
+
+    pub fn is_bold(&self) -> bool {
+        self.attributes.has(Attribute::Bold)
+    }
+
+    /// Returns the `crossterm` style of the cell.
+    pub fn style(&self) -> ContentStyle {
+        ContentStyle {
+            foreground_color: Some(self.fg),
+            background_color: Some(self.bg),
+            underline_color: None,
+            attributes: self.attributes,
+        }
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Cell {
+        Cell {
+            glyph: ' ',
+            fg: Color::Reset,
+            bg: Color::Reset,
+            attributes: Attributes::default(),
+        }
+    }
+}
+
+/// A grid of `Cell`s representing a full snapshot of something drawn
+/// on screen.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub rows: u16,
+    pub cols: u16,
+    cells: Vec<Cell>,
+}
+
+impl Frame {
+    pub fn new(rows: u16, cols: u16) -> Frame {
+        Frame {
+            rows,
+            cols,
+            cells: vec![Cell::default(); rows as usize * cols as usize],
+        }
+    }
+
+    /// Sets the cell at `(row, col)`. Positions outside of the frame
+    /// are ignored.
+    pub fn set(&mut self, row: u16, col: u16, cell: Cell) {
+        if row < self.rows && col < self.cols {
+            self.cells[row as usize * self.cols as usize + col as usize] = cell;
+        }
+    }
+
+    /// Sets the grid cell at `(row, col)` where every grid cell takes up
+    /// `cell_width` columns of the frame.
+    pub fn put(&mut self, row: u16, col: u16, cell: Cell, cell_width: u16) {
+        if cell_width < 2 {
+            return self.set(row, col, cell);
+        }
+        let (first, second) = widen(cell);
+        self.set(row, col * 2, first);
+        if let Some(second) = second {
+            self.set(row, col * 2 + 1, second);
+        }
+    }
+
+    /// Writes `content` starting at `(row, col)` using its style. Colors
+    /// that the style leaves unset keep the colors already in the frame.
+    /// Returns the column after the last written character.
+    pub fn print<D: Display>(&mut self, row: u16, col: u16, content: &StyledContent<D>) -> u16 {
+        let style = content.style();
+        let mut col = col;
+        for glyph in content.content().to_string().chars() {
+            let current = self.get(row, col);
+            self.set(
+                row,
+                col,
+                Cell {
+                    glyph,
+                    fg: style.foreground_color.unwrap_or(current.fg),
+                    bg: style.background_color.unwrap_or(current.bg),
+                    attributes: style.attributes,
+                },
+            );
+            col += 1;
+        }
+        col
+    }
+
+    /// Fills the rectangle from `(start_row, start_col)` to
+    /// `(end_row, end_col)`, inclusive, with `cell`.
+    pub fn fill(&mut self, start_row: u16, start_col: u16, end_row: u16, end_col: u16, cell: Cell) {
+        for r in start_row..=end_row {
+            for c in start_col..=end_col {
+                self.set(r, c, cell);
+            }
+        }
+    }
+
+    /// Copies every cell of `other` into this frame with its top left
+    /// corner at `(row, col)`.
+    pub fn blit(&mut self, other: &Frame, row: u16, col: u16) {
+        for r in 0..other.rows {
+            for c in 0..other.cols {
+                self.set(row + r, col + c, other.get(r, c));
+            }
+        }
+    }
+
+    pub fn get(&self, row: u16, col: u16) -> Cell {
+        if row < self.rows && col < self.cols {
+            self.cells[row as usize * self.cols as usize + col as usize]
+        } else {
+            Cell::default()
+        }
+    }
+
+    pub fn to_ansi(&self) -> String {
+        let mut out = String::new();
+        for r in 0..self.rows {
+            for c in 0..self.cols {
+                let cell = self.get(r, c);
+                if cell == Cell::default() {
+                    out.push(' ');
+                } else {
+                    out += &cell.style().apply(cell.glyph).to_string();
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn to_html(&self, title: &str) -> String {
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body style=\"background:{BACKGROUND}\">\n<pre style=\"color:{FOREGROUND};background:{BACKGROUND};line-height:1;font-family:monospace\">\n",
+            escape(title)
+        );
+        for r in 0..self.rows {
+            for c in 0..self.cols {
+                let cell = self.get(r, c);
+                out += &format!(
+                    "<span style=\"color:{};background:{};font-weight:{}\">{}</span>",
+                    hex(cell.fg, FOREGROUND),
+                    hex(cell.bg, BACKGROUND),
+                    if cell.is_bold() { "bold" } else { "normal" },
+                    escape(&cell.glyph.to_string())
+                );
+            }
+            out.push('\n');
+        }
+        out += "</pre>\n</body>\n</html>\n";
+        out
+    }
+
+    pub fn to_svg(&self) -> String {
+        let width = self.cols * SVG_CELL_WIDTH;
+        let height = self.rows * SVG_CELL_HEIGHT;
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n<rect width=\"100%\" height=\"100%\" fill=\"{BACKGROUND}\"/>\n<g font-family=\"monospace\" font-size=\"{}\" text-anchor=\"middle\">\n",
+            SVG_CELL_HEIGHT * 3 / 4
+        );
+        for r in 0..self.rows {
+            for c in 0..self.cols {
+                let cell = self.get(r, c);
+                let x = c * SVG_CELL_WIDTH;
+                let y = r * SVG_CELL_HEIGHT;
+                if cell.bg != Color::Reset {
+                    out += &format!(
+                        "<rect x=\"{x}\" y=\"{y}\" width=\"{SVG_CELL_WIDTH}\" height=\"{SVG_CELL_HEIGHT}\" fill=\"{}\"/>\n",
+                        hex(cell.bg, BACKGROUND)
+                    );
+                }
+                if cell.glyph != ' ' {
+                    out += &format!(
+                        "<text x=\"{}\" y=\"{}\" fill=\"{}\"{}>{}</text>\n",
+                        x + SVG_CELL_WIDTH / 2,
+                        y + SVG_CELL_HEIGHT * 3 / 4,
+                        hex(cell.fg, FOREGROUND),
+                        if cell.is_bold() {
+                            " font-weight=\"bold\""
+                        } else {
+                            ""
+                        },
+                        escape(&cell.glyph.to_string())
+                    );
+                }
+            }
+        }
+        out += "</g>\n</svg>\n";
+        out
+    }
+}
+
+/// Splits a cell into the two terminal columns used to draw it in double
+/// width mode. Horizontal laser beams continue across both columns and
+/// filled cells are padded with their background. The second column is
+/// `None` when whatever is underneath it should show through.
+pub fn widen(cell: Cell) -> (Cell, Option<Cell>) {
+    let second = |glyph| Some(Cell { glyph, ..cell });
+    match cell.glyph {
+        '-' => (cell, second('-')),
+        '>' => (Cell { glyph: '-', ..cell }, second('>')),
+        '<' => (cell, second('-')),
+        _ if cell.bg != Color::Reset => (cell, second(' ')),
+        _ => (cell, None),
+    }
+}
+
+/// Renders the `level` in the given `state` to a `String` in `format`.
+pub fn render(
+    level: &Level,
+    format: RenderFormat,
+    state: RenderState,
+) -> Result<String, &'static str> {
+    let mut level = level.clone();
+    level.update();
+    if let RenderState::Solved = state {
+        let solution = match solver::solve(&level, solver::DEFAULT_STATE_LIMIT) {
+            Some(s) => s,
+            None => return Err("Could not find a solution for this level."),
+        };
+        for control in solution.iter() {
+            level.apply(control);
+            level.update();
+        }
+    }
+    let frame = level.frame();
+    Ok(match format {
+        RenderFormat::Svg => frame.to_svg(),
+        RenderFormat::Html => frame.to_html(&level.info.name),
+        RenderFormat::Ansi => frame.to_ansi(),
+    })
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn hex(color: Color, default: &str) -> String {
+    match color {
+        Color::Rgb { r, g, b } => format!("#{r:02x}{g:02x}{b:02x}"),
+        Color::Black => "#000000".to_string(),
+        Color::DarkGrey => "#808080".to_string(),
+        Color::Red => "#ff0000".to_string(),
+        Color::DarkRed => "#800000".to_string(),
+        Color::Green => "#00ff00".to_string(),
+        Color::DarkGreen => "#008000".to_string(),
+        Color::Yellow => "#ffff00".to_string(),
+        Color::DarkYellow => "#808000".to_string(),
+        Color::Blue => "#0000ff".to_string(),
+        Color::DarkBlue => "#000080".to_string(),
+        Color::Magenta => "#ff00ff".to_string(),
+        Color::DarkMagenta => "#800080".to_string(),
+        Color::Cyan => "#00ffff".to_string(),
+        Color::DarkCyan => "#008080".to_string(),
+        Color::White => "#ffffff".to_string(),
+        Color::Grey => "#c0c0c0".to_string(),
+        _ => default.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn level_file_without_header_renders() {
+        let level = Level::file(PathBuf::from("examples/repo/levels/test_level3.l1t")).unwrap();
+        assert_eq!(level.info.name, "test_level3");
+        let html = render(&level, RenderFormat::Html, RenderState::Initial).unwrap();
+        assert!(html.contains("<title>test_level3</title>"));
+        let ansi = render(&level, RenderFormat::Ansi, RenderState::Initial).unwrap();
+        assert_eq!(ansi.lines().count(), level.rows as usize);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::render::Frame;
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{Print, ResetColor},
+    terminal::{self, Clear, ClearType},
+};
+use std::{
+    io::{stdout, Write},
+    sync::{Arc, Mutex},
+};
+
+/// A backend that screens are presented to. Every screen of the game is
+/// built as a `Frame` and handed to the active renderer, so alternative
+/// frontends only need to implement this trait.
+pub trait Renderer: Send {
+    /// Returns the `(cols, rows)` available to draw in.
+    fn size(&self) -> (u16, u16);
+
+    /// Presents `frame`, replacing whatever was presented before.
+    fn present(&mut self, frame: &Frame) -> crossterm::Result<()>;
+}
+
+/// Draws frames to the terminal with `crossterm`, only writing the cells
+/// that changed since the last frame.
+#[derive(Default)]
+pub struct TerminalRenderer {
+    previous: Option<Frame>,
+}
+
+impl TerminalRenderer {
+    pub fn new() -> TerminalRenderer {
+        TerminalRenderer { previous: None }
+    }
+}
+
+impl Renderer for TerminalRenderer {
+    fn size(&self) -> (u16, u16) {
+        terminal::size().unwrap_or((0, 0))
+    }
+
+    fn present(&mut self, frame: &Frame) -> crossterm::Result<()> {
+        let mut stdout = stdout();
+        let previous = match self.previous.take() {
+            Some(p) if p.rows == frame.rows && p.cols == frame.cols => p,
+            _ => {
+                queue!(stdout, ResetColor, Clear(ClearType::All))?;
+                Frame::new(frame.rows, frame.cols)
+            }
+        };
+        for r in 0..frame.rows {
+            for c in 0..frame.cols {
+                let cell = frame.get(r, c);
+                if cell == previous.get(r, c) {
+                    continue;
+                }
+                queue!(stdout, MoveTo(c, r), Print(cell.style().apply(cell.glyph)),)?;
+            }
+        }
+        stdout.flush()?;
+        self.previous = Some(frame.clone());
+        Ok(())
+    }
+}
+
+/// Keeps every presented frame in memory instead of drawing it, so that
+/// screens can be inspected and snapshot tested.
+pub struct MemoryRenderer {
+    size: (u16, u16),
+    frames: Arc<Mutex<Vec<Frame>>>,
+}
+
+impl MemoryRenderer {
+    pub fn new(cols: u16, rows: u16) -> MemoryRenderer {
+        MemoryRenderer {
+            size: (cols, rows),
+            frames: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Returns a handle to the list of presented frames that stays valid
+    /// after the renderer is installed with `set_renderer`.
+    pub fn frames(&self) -> Arc<Mutex<Vec<Frame>>> {
+        Arc::clone(&self.frames)
+    }
+}
+
+impl Renderer for MemoryRenderer {
+    fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    fn present(&mut self, frame: &Frame) -> crossterm::Result<()> {
+        if let Ok(mut frames) = self.frames.lock() {
+            frames.push(frame.clone());
+        }
+        Ok(())
+    }
+}
+
+struct Screen {
+    renderer: Option<Box<dyn Renderer>>,
+    last: Option<Frame>,
+}
+
+static SCREEN: Mutex<Screen> = Mutex::new(Screen {
+    renderer: None,
+    last: None,
+});
+
+fn with_screen<T>(f: impl FnOnce(&mut Box<dyn Renderer>, &mut Option<Frame>) -> T) -> T {
+    let mut screen = match SCREEN.lock() {
+        Ok(s) => s,
+        Err(e) => e.into_inner(),
+    };
+    let screen = &mut *screen;
+    let renderer = screen
+        .renderer
+        .get_or_insert_with(|| Box::new(TerminalRenderer::new()));
+    f(renderer, &mut screen.last)
+}
+
+/// Replaces the active renderer. The `TerminalRenderer` is used until
+/// this is called.
+pub fn set_renderer(renderer: Box<dyn Renderer>) {
+    with_screen(|r, last| {
+        *r = renderer;
+        *last = None;
+    });
+}
+
+/// Returns the `(cols, rows)` of the active renderer.
+pub fn size() -> (u16, u16) {
+    with_screen(|r, _| r.size())
+}
+
+/// Returns an empty frame the size of the active renderer.
+pub fn blank() -> Frame {
+    let (cols, rows) = size();
+    Frame::new(rows, cols)
+}
+
+/// Returns a copy of the last presented frame, or an empty frame if
+/// nothing has been presented yet or the screen size changed. Useful for
+/// drawing dialogs on top of the current screen.
+pub fn last() -> Frame {
+    with_screen(|r, last| {
+        let (cols, rows) = r.size();
+        match last {
+            Some(f) if f.rows == rows && f.cols == cols => f.clone(),
+            _ => Frame::new(rows, cols),
+        }
+    })
+}
+
+/// Presents `frame` with the active renderer.
+pub fn present(frame: &Frame) -> crossterm::Result<()> {
+    with_screen(|r, last| {
+        *last = Some(frame.clone());
+        r.present(frame)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+
+    /// Returns the glyphs of every row of `frame`.
+    fn rows(frame: &Frame) -> Vec<String> {
+        (0..frame.rows)
+            .map(|r| (0..frame.cols).map(|c| frame.get(r, c).glyph).collect())
+            .collect()
+    }
+
+    #[test]
+    fn level_is_presented_to_memory_renderer() {
+        let renderer = MemoryRenderer::new(60, 20);
+        let frames = renderer.frames();
+        set_renderer(Box::new(renderer));
+        let level = Level::core(0).unwrap();
+        level.draw().unwrap();
+
+        let frames = frames.lock().unwrap();
+        assert_eq!(frames.len(), 1);
+        let frame = &frames[0];
+        assert_eq!((frame.cols, frame.rows), (60, 20));
+        let rows: Vec<String> = rows(frame).iter().map(|r| r.trim().to_string()).collect();
+        assert_eq!(
+            rows[3..13],
+            [
+                "Level 1 by alex-laycalvert",
+                "The First Level",
+                "",
+                "IIIIIIIIIIIIIIIII",
+                "I               I",
+                "I\\           /  I",
+                "I               I",
+                "IL     X     S  I",
+                "I               I",
+                "IIIIIIIIIIIIIIIII",
+            ]
+        );
+        assert!(rows[14].starts_with("Moves: 0  Actions: 0"));
+    }
+}
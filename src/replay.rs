@@ -0,0 +1,101 @@
+use crate::{
+    controls::Control,
+    level::{Level, LevelSource},
+};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+/// Every gameplay `Control` applied while playing a level, in order, so
+/// the attempt can be played back exactly.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub source: LevelSource,
+    pub name: String,
+    /// The `Level::hash` of the level the replay was recorded on.
+    pub hash: String,
+    pub controls: Vec<Control>,
+    pub has_won: bool,
+}
+
+/// Returns the 64-bit FNV-1a hash of `content`. Used instead of the
+/// standard library's hasher because it must stay the same across Rust
+/// versions for replay files to stay valid.
+pub fn content_hash(content: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in content.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+impl Replay {
+    pub fn read(path: &PathBuf) -> Result<Replay, String> {
+        let content = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("{}: {e}", path.to_string_lossy())),
+        };
+        serde_json::from_str(&content).map_err(|e| format!("{}: {e}", path.to_string_lossy()))
+    }
+
+    pub fn write(&self, path: &PathBuf) -> Result<(), String> {
+        let content = match serde_json::to_string(self) {
+            Ok(c) => c,
+            Err(e) => return Err(e.to_string()),
+        };
+        fs::write(path, content).map_err(|e| e.to_string())
+    }
+
+    /// Writes the replay to `$HOME/.l1t/replays/` with a name made from the
+    /// current time and the level's name. Returns the path written to.
+    pub fn save(&self, home_dir: String) -> Result<PathBuf, String> {
+        let dir = PathBuf::from(home_dir + "/.l1t/replays");
+        if let Err(e) = fs::create_dir_all(&dir) {
+            return Err(e.to_string());
+        }
+        let name: String = self
+            .name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let path = dir.join(format!(
+            "{}-{name}.l1t_replay",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        self.write(&path)?;
+        Ok(path)
+    }
+
+    /// Returns an error if `level` is not the level the replay was
+    /// recorded on.
+    pub fn check(&self, level: &Level) -> Result<(), String> {
+        if self.hash != level.hash() {
+            return Err(format!(
+                "`{}` has changed since this replay was recorded",
+                level.info.name
+            ));
+        }
+        Ok(())
+    }
+
+    /// Runs the replay on `level` without drawing anything and returns
+    /// whether it wins the level.
+    pub fn verify(&self, level: &mut Level) -> Result<bool, String> {
+        self.check(level)?;
+        level.update();
+        for control in self.controls.iter() {
+            if !level.play_state().is_playing {
+                break;
+            }
+            level.step(control);
+            level.update();
+        }
+        Ok(level.play_state().has_won)
+    }
+}
@@ -1,63 +1,381 @@
-use crate::level::{LevelInfo, LevelSource};
-use serde::Deserialize;
-use std::error::Error;
+use crate::{
+    cache::{self, CacheStatus, Patience},
+    level::{Level, LevelInfo, LevelMetadata, LevelSource},
+    replay,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, Clone)]
 pub struct Repository {
     pub name: String,
     pub url: String,
     pub levels: Vec<LevelInfo>,
+    /// Where the level listing was last loaded from, or `None` if it
+    /// hasn't been loaded.
+    pub cache_status: Option<CacheStatus>,
+    /// Why entries of the level listing were left out when it was last
+    /// loaded.
+    pub warnings: Vec<String>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RepositoryLevelInfo {
     pub source: String,
     pub name: String,
     pub author: String,
     pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub par: Option<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// `replay::content_hash` of the level file, written in hexadecimal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// The size of the level file in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+impl RepositoryLevelInfo {
+    /// Returns the listing entry for a level at `source` described by
+    /// `info`.
+    pub fn new(source: String, info: LevelInfo) -> RepositoryLevelInfo {
+        RepositoryLevelInfo {
+            source,
+            name: info.name,
+            author: info.author,
+            description: info.description,
+            par: info.metadata.par,
+            tags: info.metadata.tags,
+            difficulty: info.metadata.difficulty,
+            date: info.metadata.date,
+            hash: None,
+            size: None,
+        }
+    }
+
+    /// Returns an error if the entry can't be used, because it is missing
+    /// its name or its `source` isn't a path inside the repository.
+    pub fn check(&self) -> Result<(), &'static str> {
+        let source = self.source.trim();
+        if source.is_empty() {
+            return Err("missing a source");
+        }
+        if source.starts_with('/') || source.contains("://") || source.contains('\\') {
+            return Err("the source must be a relative path");
+        }
+        if source.split('/').any(|part| part == "..") {
+            return Err("the source is outside of the repository");
+        }
+        if self.name.trim().is_empty() {
+            return Err("missing a name");
+        }
+        Ok(())
+    }
+
+    /// Returns the info of the level this entry describes, which is found
+    /// at `source`.
+    pub fn level_info(&self, source: LevelSource) -> LevelInfo {
+        LevelInfo {
+            source,
+            name: self.name.to_string(),
+            author: self.author.to_string(),
+            description: self.description.to_string(),
+            metadata: LevelMetadata {
+                par: self.par,
+                tags: self.tags.clone(),
+                difficulty: self.difficulty,
+                date: self.date.clone(),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RepositoryResponse {
     pub levels: Vec<RepositoryLevelInfo>,
 }
 
+/// A level file in a directory that is served as a repository.
+#[derive(Debug, Clone)]
+pub struct RepositoryEntry {
+    /// The level's entry in the listing, with the file name as its
+    /// `source`.
+    pub info: RepositoryLevelInfo,
+    /// The level's grid, which is what the repository serves at
+    /// `source`.
+    pub grid: String,
+}
+
 impl Repository {
     pub fn new(name: String, url: String) -> Repository {
         Repository {
             name,
             url,
             levels: vec![],
+            cache_status: None,
+            warnings: vec![],
         }
     }
 
+    /// Downloads the level listing. Entries that are missing values or
+    /// have a `source` outside of the repository are left out, with the
+    /// reason added to `warnings`. Returns an error if the listing can't
+    /// be downloaded or isn't a listing at all.
     pub async fn download_listing(&mut self) -> Result<(), Box<dyn Error>> {
-        let response = reqwest::get(self.url.to_string() + "/l1t")
-            .await?
-            .text()
-            .await?;
-        let response: RepositoryResponse = match serde_json::from_str(&response) {
-            Ok(d) => d,
-            Err(e) => {
-                eprintln!("Error: {e}");
-                RepositoryResponse { levels: vec![] }
-            }
+        let url = self.url.to_string() + "/l1t";
+        let (response, cache_status) = Repository::fetch(&url, Patience::Full).await?;
+        let response: serde_json::Value = match serde_json::from_str(&response) {
+            Ok(r) => r,
+            Err(e) => return Err(format!("{url}: not a level listing ({e})").into()),
         };
-        self.levels = response
-            .levels
-            .iter()
-            .map(|i| LevelInfo {
-                source: LevelSource::Url(self.url.to_string() + "/" + &i.source),
-                name: i.name.to_string(),
-                author: i.author.to_string(),
-                description: i.description.to_string(),
-            })
-            .collect();
+        let entries = match response.get("levels").and_then(|l| l.as_array()) {
+            Some(e) => e,
+            None => return Err(format!("{url}: not a level listing (no `levels` list)").into()),
+        };
+        let mut levels: Vec<LevelInfo> = vec![];
+        let mut warnings: Vec<String> = vec![];
+        for (i, entry) in entries.iter().enumerate() {
+            let entry: RepositoryLevelInfo = match serde_json::from_value(entry.clone()) {
+                Ok(e) => e,
+                Err(e) => {
+                    warnings.push(format!("level {}: {e}", i + 1));
+                    continue;
+                }
+            };
+            if let Err(e) = entry.check() {
+                warnings.push(format!("level {} ({}): {e}", i + 1, entry.source));
+                continue;
+            }
+            let source = LevelSource::Url(self.url.to_string() + "/" + &entry.source);
+            levels.push(entry.level_info(source));
+        }
+        self.cache_status = Some(cache_status);
+        self.levels = levels;
+        self.warnings = warnings;
         Ok(())
     }
 
-    pub async fn download_from_url(url: String) -> Result<String, Box<dyn Error>> {
-        let response = reqwest::get(url).await?.text().await?;
+    pub async fn download_from_url(
+        url: String,
+        patience: Patience,
+    ) -> Result<String, Box<dyn Error>> {
+        let (response, _) = Repository::fetch(&url, patience).await?;
         Ok(response)
     }
+
+    /// Reads every `.l1t` file in `dir` as an entry of a repository
+    /// listing. Files that can't be loaded are left out and returned as
+    /// errors alongside the entries.
+    pub fn read_dir(dir: &Path) -> Result<(Vec<RepositoryEntry>, Vec<String>), String> {
+        let mut entries: Vec<RepositoryEntry> = vec![];
+        let mut errors: Vec<String> = vec![];
+        for info in Level::available_levels(dir)? {
+            let path = match &info.source {
+                LevelSource::File(path) => path.clone(),
+                _ => continue,
+            };
+            let grid = match Level::file_grid(&path) {
+                Ok(g) => g,
+                Err(e) => {
+                    errors.push(format!("{}: {e}", path.to_string_lossy()));
+                    continue;
+                }
+            };
+            let source = path.file_name().unwrap_or_default().to_string_lossy();
+            let mut info = RepositoryLevelInfo::new(source.to_string(), info);
+            info.hash = Some(format!("{:016x}", replay::content_hash(&grid)));
+            info.size = Some(grid.len());
+            entries.push(RepositoryEntry { info, grid });
+        }
+        Ok((entries, errors))
+    }
+
+    /// Builds the `l1t` listing of the repository in `dir` from every
+    /// `.l1t` file in it and its subdirectories. Each level is described
+    /// by the header of its file, or by its entry in the current listing
+    /// if the file only contains the grid. Entries keep their order in the
+    /// current listing and new levels are added to the end.
+    ///
+    /// Every problem found is returned together as the error, such as a
+    /// level that can't be loaded, a level that isn't described anywhere
+    /// or an entry in the current listing whose file doesn't exist.
+    pub fn build_listing(dir: &Path) -> Result<RepositoryResponse, String> {
+        let listing_file = dir.join("l1t");
+        let current: Vec<RepositoryLevelInfo> = match fs::read_to_string(&listing_file) {
+            Ok(content) => match serde_json::from_str::<RepositoryResponse>(&content) {
+                Ok(r) => r.levels,
+                Err(e) => return Err(format!("{}: {e}", listing_file.to_string_lossy())),
+            },
+            Err(_) => vec![],
+        };
+        let mut files: Vec<String> = vec![];
+        level_files(dir, "", &mut files)?;
+
+        let mut errors: Vec<String> = vec![];
+        let mut sources: Vec<String> = vec![];
+        for entry in current.iter() {
+            if sources.contains(&entry.source) {
+                errors.push(format!("{}: listed more than once", entry.source));
+            } else if !files.contains(&entry.source) {
+                errors.push(format!(
+                    "{}: listed but the file doesn't exist",
+                    entry.source
+                ));
+            } else {
+                sources.push(entry.source.to_string());
+            }
+        }
+        for file in files {
+            if !sources.contains(&file) {
+                sources.push(file);
+            }
+        }
+
+        let mut levels: Vec<RepositoryLevelInfo> = vec![];
+        for source in sources {
+            let path = dir.join(&source);
+            let content = match fs::read_to_string(&path) {
+                Ok(c) => c,
+                Err(e) => {
+                    errors.push(format!("{source}: {e}"));
+                    continue;
+                }
+            };
+            let listed = current.iter().find(|e| e.source == source);
+            if !LevelInfo::has_header(&content) && listed.is_none() {
+                errors.push(format!(
+                    "{source}: only has a grid, so it needs a name, author and description"
+                ));
+                continue;
+            }
+            let file_source = LevelSource::File(path);
+            let info = match listed {
+                Some(entry) => entry.level_info(file_source),
+                None => LevelInfo {
+                    source: file_source,
+                    name: String::new(),
+                    author: String::new(),
+                    description: String::new(),
+                    metadata: LevelMetadata::default(),
+                },
+            };
+            let level = match Level::from_repository(&content, info) {
+                Ok(l) => l,
+                Err(e) => {
+                    errors.push(format!("{source}: {e}"));
+                    continue;
+                }
+            };
+            let grid = content.trim().lines().rev().take(level.rows as usize);
+            let problem =
+                if level.info.name.trim().is_empty() || level.info.author.trim().is_empty() {
+                    Some("missing a name or author")
+                } else if !grid
+                    .map(|l| l.trim_end_matches('\r').len())
+                    .all(|w| w == level.cols as usize)
+                {
+                    Some("the rows of the grid aren't all the same width")
+                } else if level.player_index.is_none() {
+                    Some("the grid has no player")
+                } else {
+                    None
+                };
+            if let Some(problem) = problem {
+                errors.push(format!("{source}: {problem}"));
+                continue;
+            }
+            let mut entry = RepositoryLevelInfo::new(source, level.info);
+            entry.hash = Some(format!("{:016x}", replay::content_hash(&content)));
+            entry.size = Some(content.len());
+            levels.push(entry);
+        }
+        if !errors.is_empty() {
+            return Err(errors.join("\n"));
+        }
+        Ok(RepositoryResponse { levels })
+    }
+
+    /// Returns the path that a repository or level URL points to if it is
+    /// a `file://` URI or a path on disk instead of a web address. Paths
+    /// starting with `~/` are in the home directory.
+    pub fn local_path(url: &str) -> Option<PathBuf> {
+        if let Some(uri) = url.strip_prefix("file://") {
+            let path = uri.strip_prefix("localhost").unwrap_or(uri);
+            return Some(PathBuf::from(percent_decode(path)));
+        }
+        if url.contains("://") {
+            return None;
+        }
+        match (url.strip_prefix("~/"), home::home_dir()) {
+            (Some(rest), Some(home)) => Some(home.join(rest)),
+            _ => Some(PathBuf::from(url)),
+        }
+    }
+
+    /// Reads the content at `url` from disk if it is local, or downloads
+    /// it through the cache otherwise, trying as hard as `patience` allows.
+    async fn fetch(url: &str, patience: Patience) -> Result<(String, CacheStatus), Box<dyn Error>> {
+        match Repository::local_path(url) {
+            Some(path) => match fs::read_to_string(&path) {
+                Ok(content) => Ok((content, CacheStatus::Local)),
+                Err(e) => Err(format!("{}: {e}", path.to_string_lossy()).into()),
+            },
+            None => cache::get(url, patience).await,
+        }
+    }
+}
+
+/// Adds the path of every `.l1t` file in `dir` and its subdirectories to
+/// `files`, relative to the repository and separated by `/`. `prefix` is
+/// the path of `dir` in the repository.
+fn level_files(dir: &Path, prefix: &str, files: &mut Vec<String>) -> Result<(), String> {
+    let name = |path: &Path| {
+        path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    };
+    for info in Level::available_levels(dir)? {
+        if let LevelSource::File(path) = info.source {
+            files.push(prefix.to_string() + &name(&path));
+        }
+    }
+    for subdir in Level::available_dirs(dir)? {
+        let prefix = prefix.to_string() + &name(&subdir) + "/";
+        level_files(&subdir, &prefix, files)?;
+    }
+    Ok(())
+}
+
+/// Decodes the `%XX` escapes in a URI, such as `%20` for a space.
+pub(crate) fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out: Vec<u8> = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = s
+            .get(i + 1..i + 3)
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
 }
@@ -0,0 +1,172 @@
+use crate::{
+    replay,
+    repository::{self, Repository, RepositoryResponse},
+};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// The most bytes of a request that are read, which is plenty for the
+/// request line and headers that matter here.
+const MAX_REQUEST: usize = 8192;
+
+/// A response to a request, before it is written to the connection.
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn text(status: u16, body: &str) -> Response {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.to_string() + "\n",
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        304 => "Not Modified",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Serves every `.l1t` file in `dir` as a repository on `port` of every
+/// network interface. The `l1t` listing is generated from the headers of
+/// the level files each time it is requested, so levels can be added or
+/// changed without restarting the server.
+pub async fn serve(dir: PathBuf, port: u16) -> Result<(), Box<dyn Error>> {
+    if !dir.is_dir() {
+        return Err(format!("{}: not a directory", dir.to_string_lossy()).into());
+    }
+    let (entries, errors) = Repository::read_dir(&dir)?;
+    for error in errors {
+        eprintln!("skipping {error}");
+    }
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    println!(
+        "Serving {} levels from {} at http://0.0.0.0:{port}",
+        entries.len(),
+        dir.to_string_lossy()
+    );
+    let dir = Arc::new(dir);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let dir = dir.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &dir).await {
+                eprintln!("{e}");
+            }
+        });
+    }
+}
+
+/// Reads a single request from `stream` and writes the response, then
+/// closes the connection.
+async fn handle(mut stream: TcpStream, dir: &Path) -> std::io::Result<()> {
+    let mut buf = vec![0; MAX_REQUEST];
+    let mut len = 0;
+    while len < buf.len() {
+        let n = stream.read(&mut buf[len..]).await?;
+        if n == 0 {
+            break;
+        }
+        len += n;
+        if buf[..len].windows(4).any(|w| w == b"\r\n\r\n") {
+            break;
+        }
+    }
+    let request = String::from_utf8_lossy(&buf[..len]);
+    let mut lines = request.lines();
+    let mut request_line = lines.next().unwrap_or("").split_whitespace();
+    let method = request_line.next().unwrap_or("");
+    let target = request_line.next().unwrap_or("/");
+    let if_none_match = lines.find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if name.trim().eq_ignore_ascii_case("if-none-match") {
+            Some(value.trim().to_string())
+        } else {
+            None
+        }
+    });
+
+    let response = match method {
+        "GET" | "HEAD" => route(dir, target),
+        _ => Response::text(405, "Method Not Allowed"),
+    };
+    let etag = format!("\"{:016x}\"", replay::content_hash(&response.body));
+    let status = if response.status == 200 && if_none_match.as_deref() == Some(etag.as_str()) {
+        304
+    } else {
+        response.status
+    };
+    println!("{method} {target} {status}");
+
+    let mut head = format!("HTTP/1.1 {status} {}\r\n", reason(status));
+    if status != 304 {
+        head += &format!(
+            "Content-Type: {}\r\nContent-Length: {}\r\n",
+            response.content_type,
+            response.body.len()
+        );
+    }
+    if response.status == 200 {
+        head += &format!("ETag: {etag}\r\n");
+    }
+    if status == 405 {
+        head += "Allow: GET, HEAD\r\n";
+    }
+    head += "Connection: close\r\n\r\n";
+    stream.write_all(head.as_bytes()).await?;
+    if method != "HEAD" && status != 304 {
+        stream.write_all(response.body.as_bytes()).await?;
+    }
+    stream.shutdown().await
+}
+
+/// Returns the listing for `/l1t` and the grid of a level for the path
+/// that is its `source` in the listing.
+fn route(dir: &Path, target: &str) -> Response {
+    let path = target.split(['?', '#']).next().unwrap_or("/");
+    let path = repository::percent_decode(path.trim_start_matches('/'));
+    let (entries, errors) = match Repository::read_dir(dir) {
+        Ok(e) => e,
+        Err(e) => return Response::text(500, &e),
+    };
+    if path == "l1t" {
+        for error in errors {
+            eprintln!("skipping {error}");
+        }
+        let listing = RepositoryResponse {
+            levels: entries.into_iter().map(|e| e.info).collect(),
+        };
+        return match serde_json::to_string_pretty(&listing) {
+            Ok(body) => Response {
+                status: 200,
+                content_type: "application/json",
+                body,
+            },
+            Err(e) => Response::text(500, &e.to_string()),
+        };
+    }
+    match entries.into_iter().find(|e| e.info.source == path) {
+        Some(entry) => Response {
+            status: 200,
+            content_type: "text/plain; charset=utf-8",
+            body: entry.grid,
+        },
+        None => Response::text(404, "Not Found"),
+    }
+}
@@ -0,0 +1,184 @@
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path, sync::RwLock};
+
+/// The color scheme used to highlight menus.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Classic,
+    Amber,
+    Ocean,
+    Forest,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 4] = [Theme::Classic, Theme::Amber, Theme::Ocean, Theme::Forest];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::Classic => "classic",
+            Theme::Amber => "amber",
+            Theme::Ocean => "ocean",
+            Theme::Forest => "forest",
+        }
+    }
+
+    /// Returns the background color of selected menu items and titles.
+    pub fn accent(&self) -> Color {
+        match self {
+            Theme::Classic => Color::White,
+            Theme::Amber => Color::Rgb {
+                r: 255,
+                g: 176,
+                b: 0,
+            },
+            Theme::Ocean => Color::Rgb {
+                r: 0,
+                g: 175,
+                b: 255,
+            },
+            Theme::Forest => Color::Rgb {
+                r: 95,
+                g: 215,
+                b: 95,
+            },
+        }
+    }
+
+    /// Returns the theme `offset` places after this one in `Theme::ALL`,
+    /// wrapping around either end.
+    pub fn cycle(self, offset: isize) -> Theme {
+        let len = Theme::ALL.len() as isize;
+        let i = Theme::ALL.iter().position(|t| *t == self).unwrap_or(0) as isize;
+        Theme::ALL[(i + offset).rem_euclid(len) as usize]
+    }
+}
+
+/// User adjustable settings that affect how the game is played and drawn,
+/// saved to `$HOME/.l1t/config.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub theme: Theme,
+    /// Whether laser beams, statues and win/loss effects are animated.
+    pub animations: bool,
+    /// Milliseconds between each step of a laser beam animation.
+    pub animation_speed: u64,
+    /// Milliseconds to wait after a level is won or lost before showing
+    /// the message.
+    pub win_delay: u64,
+    /// Whether colors that are hard to tell apart with red-green color
+    /// blindness are replaced.
+    pub colorblind: bool,
+    /// Whether every grid cell is drawn two columns wide so that levels
+    /// look square in the terminal.
+    pub double_width: bool,
+    /// Keys bound from the settings menu, by `Control::name`, written the
+    /// same way as in the keymap file. These replace the keymap file's
+    /// keys for the same controls.
+    pub keys: BTreeMap<String, String>,
+}
+
+static SETTINGS: RwLock<Settings> = RwLock::new(Settings::DEFAULT);
+
+/// The settings as they were read from the config file, without command
+/// line flags or in-game toggles applied, which is what gets saved.
+static SAVED: RwLock<Settings> = RwLock::new(Settings::DEFAULT);
+
+impl Settings {
+    pub const DEFAULT: Settings = Settings {
+        theme: Theme::Classic,
+        animations: true,
+        animation_speed: 15,
+        win_delay: 500,
+        colorblind: false,
+        double_width: false,
+        keys: BTreeMap::new(),
+    };
+
+    /// Returns a copy of the settings currently in use.
+    pub fn current() -> Settings {
+        match SETTINGS.read() {
+            Ok(s) => s.clone(),
+            Err(_) => Settings::DEFAULT,
+        }
+    }
+
+    /// Returns the number of terminal columns each grid cell takes up.
+    pub fn cell_width(&self) -> u16 {
+        if self.double_width {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Replaces the settings currently in use.
+    pub fn set(settings: Settings) {
+        if let Ok(mut s) = SETTINGS.write() {
+            *s = settings;
+        }
+    }
+
+    /// Replaces both the settings currently in use and the settings that
+    /// are saved when a setting is changed, after they are read from the
+    /// config file.
+    pub fn load(settings: Settings) {
+        if let Ok(mut s) = SAVED.write() {
+            *s = settings.clone();
+        }
+        Settings::set(settings);
+    }
+
+    /// Applies `change` to the settings currently in use and to the
+    /// settings read from the config file, then saves the latter. Only
+    /// what `change` sets is written, so command line flags and in-game
+    /// toggles aren't saved along with it.
+    pub fn update(change: impl Fn(&mut Settings)) -> Result<(), String> {
+        let mut settings = Settings::current();
+        change(&mut settings);
+        Settings::set(settings);
+        let saved = match SAVED.write() {
+            Ok(mut s) => {
+                change(&mut s);
+                s.clone()
+            }
+            Err(_) => return Err("failed to update settings".to_string()),
+        };
+        saved.save()
+    }
+
+    /// Reads the settings from `$HOME/.l1t/config.toml`, creating the file
+    /// with the default settings if it doesn't exist. Settings missing
+    /// from the file keep their default values.
+    pub fn read(home_dir: String) -> Result<Settings, String> {
+        let file = home_dir.to_string() + "/.l1t/config.toml";
+        if !path::Path::new(&file).exists() {
+            fs::create_dir(home_dir.clone() + "/.l1t").ok();
+            Settings::DEFAULT.write(home_dir)?;
+        }
+        let content = fs::read_to_string(&file).unwrap_or_default();
+        toml::from_str(&content).map_err(|e| format!("{file}: {e}"))
+    }
+
+    /// Writes the settings to `$HOME/.l1t/config.toml`.
+    pub fn write(&self, home_dir: String) -> Result<(), String> {
+        let content = toml::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(home_dir + "/.l1t/config.toml", content).map_err(|e| e.to_string())
+    }
+
+    /// Same as `Settings::write` for the current user's home directory.
+    pub fn save(&self) -> Result<(), String> {
+        match home::home_dir() {
+            Some(home) => self.write(home.to_string_lossy().to_string()),
+            None => Err("failed to find user's home directory".to_string()),
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings::DEFAULT
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{
+    controls::Control,
+    level::{Level, Snapshot},
+};
+use std::collections::{HashMap, VecDeque};
+
+/// The default maximum number of distinct level states the solver will
+/// visit before giving up.
+pub const DEFAULT_STATE_LIMIT: usize = 100_000;
+
+const CONTROLS: [Control; 5] = [
+    Control::Up,
+    Control::Down,
+    Control::Left,
+    Control::Right,
+    Control::Action,
+];
+
+/// Finds the shortest sequence of controls that wins the `level` from its
+/// current state using a breadth-first search over every reachable level
+/// state. Returns `None` if the level cannot be won or if more than
+/// `state_limit` states would need to be visited.
+pub fn solve(level: &Level, state_limit: usize) -> Option<Vec<Control>> {
+    let mut level = level.clone();
+    level.update();
+    let state = level.play_state();
+    if state.has_won {
+        return Some(vec![]);
+    }
+    if !state.is_playing {
+        return None;
+    }
+    let start = level.snapshot();
+    let mut parents: HashMap<Snapshot, Option<(Snapshot, Control)>> = HashMap::new();
+    let mut queue: VecDeque<Snapshot> = VecDeque::new();
+    parents.insert(start.clone(), None);
+    queue.push_back(start);
+    while let Some(current) = queue.pop_front() {
+        for control in CONTROLS.iter() {
+            level.restore(&current);
+            level.apply(control);
+            level.update();
+            let next = level.snapshot();
+            if parents.contains_key(&next) {
+                continue;
+            }
+            let state = level.play_state();
+            parents.insert(next.clone(), Some((current.clone(), *control)));
+            if state.has_won {
+                return Some(path_to(&parents, next));
+            }
+            if !state.is_playing {
+                continue;
+            }
+            if parents.len() >= state_limit {
+                return None;
+            }
+            queue.push_back(next);
+        }
+    }
+    None
+}
+
+fn path_to(
+    parents: &HashMap<Snapshot, Option<(Snapshot, Control)>>,
+    end: Snapshot,
+) -> Vec<Control> {
+    let mut path = vec![];
+    let mut current = end;
+    while let Some(Some((parent, control))) = parents.get(&current) {
+        path.push(*control);
+        current = parent.clone();
+    }
+    path.reverse();
+    path
+}
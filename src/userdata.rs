@@ -1,8 +1,11 @@
 use crate::{
-    level::{LevelInfo, LevelSource},
+    keymap::Keymap,
+    level::{Level, LevelInfo, LevelLossReason, LevelResult, LevelSource, SavedLevel},
     repository::Repository,
+    settings::Settings,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::{fs, path};
 
@@ -15,11 +18,53 @@ pub struct CompletedRepoLevel {
     pub completed_at: u64,
 }
 
+/// A level completed from a `.l1t` file. The level only counts as
+/// completed while the file still has the same grid, so editing a level
+/// resets its progress.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CompletedFileLevel {
+    /// The canonical path of the file.
+    pub path: String,
+    /// The `Level::hash` of the level when it was completed.
+    pub hash: String,
+    pub name: String,
+    pub completed_at: u64,
+}
+
+/// The player's record on a single level across every attempt.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LevelScore {
+    /// Fewest moves the level was won with.
+    pub best_moves: Option<usize>,
+    /// Fastest the level was won in, in seconds.
+    pub best_time: Option<u64>,
+    /// Most stars the level was won with.
+    pub stars: usize,
+    /// Number of times the level was won, lost or quit.
+    pub attempts: usize,
+    /// Number of times the level was restarted during an attempt.
+    pub restarts: usize,
+    pub zapper_deaths: usize,
+    pub laser_deaths: usize,
+    /// Seconds spent on every finished attempt.
+    pub time_played: u64,
+    /// Moves made on every finished attempt.
+    pub moves: usize,
+    pub first_completed_at: Option<u64>,
+    pub last_completed_at: Option<u64>,
+}
+
 pub struct UserData {
     file: String,
+    repositories_file: String,
     pub completed_core_levels: Vec<usize>,
     pub completed_levels: Vec<CompletedRepoLevel>,
+    pub completed_files: Vec<CompletedFileLevel>,
     pub repositories: Vec<Repository>,
+    pub saved_levels: Vec<SavedLevel>,
+    /// The player's record on each level, by `LevelSource::key`.
+    pub scores: HashMap<String, LevelScore>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -27,32 +72,114 @@ pub struct SerializedUserData {
     file: String,
     pub completed_core_levels: Vec<usize>,
     pub completed_levels: Vec<CompletedRepoLevel>,
+    #[serde(default)]
+    pub completed_files: Vec<CompletedFileLevel>,
+    #[serde(default)]
+    pub saved_levels: Vec<SavedLevel>,
+    #[serde(default)]
+    pub scores: HashMap<String, LevelScore>,
+    /// The most stars earned on each level, as saved before they were
+    /// kept in `scores`. Read into `scores` and never written.
+    #[serde(default, skip_serializing)]
+    pub stars: HashMap<String, usize>,
 }
 
 impl UserData {
-    fn read_repositories(home_dir: String) -> Result<Vec<Repository>, String> {
-        let file = home_dir.to_string() + "/.l1t/repositories.l1t_conf";
-        if !path::Path::new(&file).exists() {
-            fs::create_dir(home_dir + "/.l1t").ok();
-            if let Err(e) = fs::write(&file, "") {
+    fn read_repositories(file: &str) -> Result<Vec<Repository>, String> {
+        if !path::Path::new(file).exists() {
+            if let Err(e) = fs::write(file, "") {
                 return Err(e.to_string());
             }
         }
-        let file_content = fs::read_to_string(&file).unwrap_or_default();
+        let file_content = fs::read_to_string(file).unwrap_or_default();
         let mut repositories: Vec<Repository> = vec![];
-        for line in file_content.trim().split('\n') {
-            let parts: Vec<&str> = line.trim().split('=').collect();
-            if parts.len() < 2 {
+        for line in file_content.lines() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
                 continue;
             }
-            repositories.push(Repository::new(
-                parts[0].trim().to_string(),
-                parts[1].trim().to_string(),
-            ));
+            if let Some((name, url)) = parse_repository_line(line) {
+                repositories.push(Repository::new(name, url));
+            }
         }
         Ok(repositories)
     }
 
+    /// Writes the repositories to `$HOME/.l1t/repositories.l1t_conf`.
+    fn write_repositories(&self) -> Result<(), String> {
+        let mut content = String::from(
+            "# l1t repositories\n#\n# Each line is `name = url`. Any `\\`, `=` or `#` in a name or URL is written\n# with a `\\` in front of it.\n",
+        );
+        for repo in self.repositories.iter() {
+            content += &format!("{} = {}\n", escape(&repo.name), escape(&repo.url));
+        }
+        fs::write(&self.repositories_file, content).map_err(|e| e.to_string())
+    }
+
+    /// Returns the index of the repository named `name`.
+    pub fn find_repository(&self, name: &str) -> Option<usize> {
+        self.repositories.iter().position(|r| r.name == name)
+    }
+
+    /// Returns an error if `name` can't be used for the repository at
+    /// `index`, or for a new repository if `index` is `None`.
+    fn check_repository_name(&self, name: &str, index: Option<usize>) -> Result<(), String> {
+        if name.is_empty() {
+            return Err("a repository needs a name".to_string());
+        }
+        match self.find_repository(name) {
+            Some(i) if Some(i) != index => {
+                Err(format!("a repository named `{name}` already exists"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Adds a repository to the end of the list and saves the list.
+    /// Relative paths to local repositories are made absolute.
+    pub fn add_repository(&mut self, name: String, url: String) -> Result<(), String> {
+        let name = name.trim().to_string();
+        let url = url.trim().trim_end_matches('/').to_string();
+        let url = match Repository::local_path(&url) {
+            Some(path) if path.is_relative() => fs::canonicalize(&path)
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or(url),
+            _ => url,
+        };
+        self.check_repository_name(&name, None)?;
+        if url.is_empty() {
+            return Err("a repository needs a URL".to_string());
+        }
+        self.repositories.push(Repository::new(name, url));
+        self.write_repositories()
+    }
+
+    pub fn remove_repository(&mut self, index: usize) -> Result<(), String> {
+        if index < self.repositories.len() {
+            self.repositories.remove(index);
+        }
+        self.write_repositories()
+    }
+
+    pub fn rename_repository(&mut self, index: usize, name: String) -> Result<(), String> {
+        let name = name.trim().to_string();
+        self.check_repository_name(&name, Some(index))?;
+        self.repositories[index].name = name;
+        self.write_repositories()
+    }
+
+    /// Moves the repository at `index` by `offset` places, stopping at
+    /// either end of the list. Returns the index it was moved to.
+    pub fn move_repository(&mut self, index: usize, offset: isize) -> Result<usize, String> {
+        let last = self.repositories.len().saturating_sub(1) as isize;
+        let to = (index as isize + offset).clamp(0, last) as usize;
+        let repo = self.repositories.remove(index);
+        self.repositories.insert(to, repo);
+        self.write_repositories()?;
+        Ok(to)
+    }
+
+    /// Reads the player's data from `$HOME/.l1t`, and starts using the
+    /// settings and keys saved in `$HOME/.l1t/config.toml`.
     pub fn read(home_dir: String) -> Result<UserData, String> {
         let file = home_dir.to_string() + "/.l1t/data.json";
         if !path::Path::new(&file).exists() {
@@ -61,6 +188,10 @@ impl UserData {
                 file: file.clone(),
                 completed_core_levels: vec![],
                 completed_levels: vec![],
+                completed_files: vec![],
+                saved_levels: vec![],
+                scores: HashMap::new(),
+                stars: HashMap::new(),
             };
             let content = match serde_json::to_string(&data) {
                 Ok(c) => c,
@@ -75,25 +206,42 @@ impl UserData {
             Ok(d) => d,
             Err(e) => return Err(e.to_string()),
         };
-        let repositories = UserData::read_repositories(home_dir)?;
+        let settings = Settings::read(home_dir.clone())?;
+        let keymap = Keymap::current()
+            .with_keys(&settings.keys)
+            .map_err(|e| format!("{home_dir}/.l1t/config.toml: {e}"))?;
+        Keymap::set(keymap);
+        Settings::load(settings);
+        let repositories_file = home_dir + "/.l1t/repositories.l1t_conf";
+        let repositories = UserData::read_repositories(&repositories_file)?;
+
+        let mut scores = data.scores;
+        for (key, stars) in data.stars {
+            let score = scores.entry(key).or_default();
+            score.stars = score.stars.max(stars);
+        }
 
         Ok(UserData {
             repositories,
+            repositories_file,
             file,
             completed_core_levels: data.completed_core_levels,
             completed_levels: data.completed_levels,
+            completed_files: data.completed_files,
+            saved_levels: data.saved_levels,
+            scores,
         })
     }
 
-    fn complete_core(&mut self, level: usize) -> Result<(), String> {
-        if self.completed_core_levels.iter().any(|i| *i == level) {
-            return Ok(());
-        }
-        self.completed_core_levels.push(level);
+    fn write(&self) -> Result<(), String> {
         let content = match serde_json::to_string(&SerializedUserData {
             file: self.file.clone(),
             completed_core_levels: self.completed_core_levels.clone(),
             completed_levels: self.completed_levels.clone(),
+            completed_files: self.completed_files.clone(),
+            saved_levels: self.saved_levels.clone(),
+            scores: self.scores.clone(),
+            stars: HashMap::new(),
         }) {
             Ok(c) => c,
             Err(e) => return Err(e.to_string()),
@@ -104,6 +252,14 @@ impl UserData {
         Ok(())
     }
 
+    fn complete_core(&mut self, level: usize) -> Result<(), String> {
+        if self.completed_core_levels.contains(&level) {
+            return Ok(());
+        }
+        self.completed_core_levels.push(level);
+        self.write()
+    }
+
     fn complete_repo(&mut self, level_info: LevelInfo) -> Result<(), String> {
         if let LevelSource::Url(url) = level_info.source {
             if self.completed_levels.iter().any(|l| {
@@ -122,26 +278,189 @@ impl UserData {
                 author: level_info.author,
                 description: level_info.description,
             });
-            let content = match serde_json::to_string(&SerializedUserData {
-                file: self.file.clone(),
-                completed_core_levels: self.completed_core_levels.clone(),
-                completed_levels: self.completed_levels.clone(),
-            }) {
-                Ok(c) => c,
-                Err(e) => return Err(e.to_string()),
-            };
-            if let Err(e) = fs::write(&self.file, content) {
-                return Err(e.to_string());
-            };
+            return self.write();
         }
         Ok(())
     }
 
-    pub fn complete(&mut self, level_info: LevelInfo) -> Result<(), String> {
-        match level_info.source {
-            LevelSource::Core(level) => self.complete_core(level),
-            LevelSource::Url(_) => self.complete_repo(level_info),
-            _ => Err("".to_string()),
+    /// Returns the canonical path of a level loaded from a file.
+    fn file_path(level: &Level) -> Option<String> {
+        match &level.info.source {
+            LevelSource::File(path) => {
+                let path = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+                Some(path.to_string_lossy().to_string())
+            }
+            _ => None,
+        }
+    }
+
+    fn complete_file(&mut self, level: &Level) -> Result<(), String> {
+        let path = match UserData::file_path(level) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let hash = level.hash();
+        if self
+            .completed_files
+            .iter()
+            .any(|l| l.path == path && l.hash == hash)
+        {
+            return Ok(());
+        }
+        let completed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.completed_files.retain(|l| l.path != path);
+        self.completed_files.push(CompletedFileLevel {
+            path,
+            hash,
+            name: level.info.name.to_string(),
+            completed_at,
+        });
+        self.write()
+    }
+
+    pub fn complete(&mut self, level: &Level) -> Result<(), String> {
+        match &level.info.source {
+            LevelSource::Core(l) => self.complete_core(*l),
+            LevelSource::Url(_) => self.complete_repo(level.info.clone()),
+            LevelSource::File(_) => self.complete_file(level),
+        }
+    }
+
+    /// Returns whether `level`, loaded from a file, has been completed
+    /// since its grid last changed.
+    pub fn is_file_completed(&self, level: &Level) -> bool {
+        let path = match UserData::file_path(level) {
+            Some(path) => path,
+            None => return false,
+        };
+        let hash = level.hash();
+        self.completed_files
+            .iter()
+            .any(|l| l.path == path && l.hash == hash)
+    }
+
+    /// Updates the score of the level `result` is from with the result of
+    /// an attempt at it.
+    pub fn record(&mut self, result: &LevelResult) -> Result<(), String> {
+        let key = result.replay.source.score_key(&result.replay.hash);
+        let score = self.scores.entry(key).or_default();
+        score.attempts += 1;
+        score.restarts += result.restarts;
+        score.time_played += result.time.as_secs();
+        score.moves += result.moves;
+        match result.reason_for_loss {
+            Some(LevelLossReason::Zapper) => score.zapper_deaths += 1,
+            Some(LevelLossReason::Death) => score.laser_deaths += 1,
+            _ => (),
+        }
+        if result.has_won {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            let time = result.time.as_secs();
+            score.best_moves = Some(
+                score
+                    .best_moves
+                    .map_or(result.moves, |m| m.min(result.moves)),
+            );
+            score.best_time = Some(score.best_time.map_or(time, |t| t.min(time)));
+            score.stars = score.stars.max(result.stars);
+            score.first_completed_at.get_or_insert(now);
+            score.last_completed_at = Some(now);
+        }
+        self.write()
+    }
+
+    /// Stores a level left with `Save & Quit`, replacing any earlier save
+    /// of the same level.
+    pub fn save_level(&mut self, saved: SavedLevel) -> Result<(), String> {
+        self.saved_levels.retain(|s| s.source != saved.source);
+        self.saved_levels.push(saved);
+        self.write()
+    }
+
+    /// Removes and returns the saved game for the level from `source`,
+    /// if there is one.
+    pub fn take_saved_level(&mut self, source: &LevelSource) -> Option<SavedLevel> {
+        let key = source.key();
+        let index = self.saved_levels.iter().position(|s| s.source == key)?;
+        let saved = self.saved_levels.remove(index);
+        self.write().ok();
+        Some(saved)
+    }
+}
+
+/// Escapes `\`, `=` and `#` and replaces line breaks with spaces so that
+/// `s` can be written on either side of a line in the repositories file.
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' | '=' | '#' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' | '\r' => out.push(' '),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Splits a line of the repositories file into the repository's name and
+/// URL at the first `=` that isn't escaped, undoing `escape` on both.
+/// Any other `\` is kept as it is, so lines written by hand before
+/// escaping existed, like `local = C:\levels\repo`, read the same as
+/// they used to. Returns `None` if the line has no `=`.
+fn parse_repository_line(line: &str) -> Option<(String, String)> {
+    let mut name = String::new();
+    let mut url = String::new();
+    let mut in_url = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        let out = if in_url { &mut url } else { &mut name };
+        match c {
+            '\\' => match chars.clone().next() {
+                Some(c @ ('\\' | '=' | '#')) => {
+                    chars.next();
+                    out.push(c);
+                }
+                _ => out.push('\\'),
+            },
+            '=' if !in_url => in_url = true,
+            c => out.push(c),
         }
     }
+    if !in_url {
+        return None;
+    }
+    Some((name.trim().to_string(), url.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repository_line_round_trips() {
+        let name = "a = b # \\ c";
+        let url = "http://example.com/?x=1#top";
+        let line = format!("{} = {}", escape(name), escape(url));
+        assert_eq!(
+            parse_repository_line(&line),
+            Some((name.to_string(), url.to_string()))
+        );
+    }
+
+    #[test]
+    fn repository_line_written_by_hand_keeps_backslashes() {
+        assert_eq!(
+            parse_repository_line("local=C:\\levels\\repo"),
+            Some(("local".to_string(), "C:\\levels\\repo".to_string()))
+        );
+    }
 }